@@ -1,6 +1,12 @@
 MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x00000000, LENGTH = 256K
+  FLASH : ORIGIN = 0x00000000, LENGTH = 248K
+  /* the last two flash pages are kept free for src/storage.rs */
+  HIGHSCORE : ORIGIN = 0x0003E000, LENGTH = 4K
+  SAVEGAME : ORIGIN = 0x0003F000, LENGTH = 4K
   RAM : ORIGIN = 0x20000000, LENGTH = 16K
 }
+
+_highscore = ORIGIN(HIGHSCORE);
+_savegame = ORIGIN(SAVEGAME);
@@ -0,0 +1,481 @@
+// tilt in mg below which the board counts as lying flat, so small wobbles don't steer
+const TILT_DEAD_ZONE: i32 = 300;
+// how much harder, in mg, a new direction has to be tilted than the current one to take over
+const TILT_HYSTERESIS: i32 = 150;
+// ticks after a tilt turn before tilting can turn the snake again
+const TILT_COOLDOWN_TICKS: u32 = 2;
+// swing in accelerometer magnitude, in mg, that counts as a shake. Lying still the lsm303agr
+// reads about 1000 (gravity) give or take 30, tilting by hand stays within a couple of hundred
+// of that, while a firm shake swings from a few hundred to well over 2000
+const SHAKE_THRESHOLD: i32 = 1200;
+// tilt in mg added up over ticks before the momentum controller turns. Held at a firm 600mg
+// that's four ticks, tipped right over on its side two
+const MOMENTUM_THRESHOLD: i32 = 1500;
+// quarters of the momentum controller's total it keeps from one tick to the next, so a brief nudge
+// fades away. Rounding towards zero takes it all the way down
+const MOMENTUM_KEPT_QUARTERS: i32 = 3;
+
+pub fn tilt_to_direction(x: i32, y: i32) -> Option<char> {
+    // heads whichever way the board is tilted the most, if it's tilted enough at all
+    if x.abs() < TILT_DEAD_ZONE && y.abs() < TILT_DEAD_ZONE {
+        return None;
+    }
+
+    if x.abs() >= y.abs() {
+        if x > 0 { Some('R') } else { Some('L') }
+    } else {
+        if y > 0 { Some('D') } else { Some('U') }
+    }
+}
+
+// how the accelerometer sits relative to the display. The resting reading is taken off first,
+// then the raw axes are negated, then swapped, so a board mounted sideways or upside down, or
+// resting on a slope, can be corrected here instead of in the mapping.
+// STANDARD is a bare micro:bit held with the display facing up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TiltAxes {
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub swap: bool,
+    pub offset: (i32, i32),  // raw x and y the board reads at rest, which count as level
+}
+
+impl TiltAxes {
+    pub const STANDARD: TiltAxes = TiltAxes { invert_x: false, invert_y: false, swap: false, offset: (0, 0) };
+
+    pub fn with_offset(self, offset: (i32, i32)) -> TiltAxes {
+        // the same axes, levelled to a resting reading, see calibrate in main
+        TiltAxes { offset, ..self }
+    }
+
+    pub fn apply(&self, x: i32, y: i32) -> (i32, i32) {
+        // raw readings in, readings as the display sees them out
+        let (x, y) = (x.saturating_sub(self.offset.0), y.saturating_sub(self.offset.1));
+        let x = if self.invert_x { -x } else { x };
+        let y = if self.invert_y { -y } else { y };
+        if self.swap { (y, x) } else { (x, y) }
+    }
+}
+
+fn tilt_along(direction: char, x: i32, y: i32) -> i32 {
+    // how far the board is tilted towards a direction, negative when it's tilted away
+    match direction {
+        'R' => x,
+        'L' => -x,
+        'D' => y,
+        'U' => -y,
+        _ => 0,
+    }
+}
+
+// tilt steering that sticks with its direction until another one clearly wins,
+// so a board held near 45 degrees doesn't flick the snake back and forth
+pub struct TiltController {
+    axes: TiltAxes,
+    last: Option<char>,
+    cooldown: u32,
+}
+
+impl TiltController {
+    pub fn new() -> TiltController {
+        TiltController::with_axes(TiltAxes::STANDARD)
+    }
+
+    pub fn with_axes(axes: TiltAxes) -> TiltController {
+        TiltController { axes, last: None, cooldown: 0 }
+    }
+
+    pub fn update(&mut self, x: i32, y: i32) -> Option<char> {
+        // call once a tick with the raw readings. None while the board is flat, otherwise the direction to steer in
+        self.cooldown = self.cooldown.saturating_sub(1);
+        let (x, y) = self.axes.apply(x, y);
+        let candidate = tilt_to_direction(x, y)?;
+
+        if let Some(last) = self.last {
+            let clear_winner = tilt_along(candidate, x, y) >= tilt_along(last, x, y) + TILT_HYSTERESIS;
+            if candidate == last || self.cooldown > 0 || !clear_winner {
+                return Some(last);
+            }
+        }
+        self.last = Some(candidate);
+        self.cooldown = TILT_COOLDOWN_TICKS;
+        Some(candidate)
+    }
+}
+
+impl Default for TiltController {
+    fn default() -> Self {
+        TiltController::new()
+    }
+}
+
+fn outside_dead_zone(reading: i32) -> i32 {
+    // a reading, or nothing at all while it's within the dead zone
+    if reading.abs() < TILT_DEAD_ZONE { 0 } else { reading }
+}
+
+// tilt steering with some weight to it: tilting pushes the snake rather than pointing it,
+// and it only turns once enough push has built up. Otherwise it carries on the way it was going
+pub struct MomentumController {
+    axes: TiltAxes,
+    push: (i32, i32),
+}
+
+impl MomentumController {
+    pub fn new() -> MomentumController {
+        MomentumController::with_axes(TiltAxes::STANDARD)
+    }
+
+    pub fn with_axes(axes: TiltAxes) -> MomentumController {
+        MomentumController { axes, push: (0, 0) }
+    }
+
+    pub fn update(&mut self, x: i32, y: i32) -> Option<char> {
+        /*
+        Call once a tick with the raw readings. Hands back a direction to turn in once the push
+        has built up past MOMENTUM_THRESHOLD, otherwise None.
+        - the push decays every tick, so it takes a steady tilt rather than a flick to turn
+        - readings within the dead zone add nothing, a board lying flat only lets the push fade
+        - turning uses the push up, it has to build up again for the next turn
+        */
+        let (x, y) = self.axes.apply(x, y);
+        self.push.0 = self.push.0 * MOMENTUM_KEPT_QUARTERS / 4 + outside_dead_zone(x);
+        self.push.1 = self.push.1 * MOMENTUM_KEPT_QUARTERS / 4 + outside_dead_zone(y);
+
+        if self.push.0.abs().max(self.push.1.abs()) < MOMENTUM_THRESHOLD {
+            return None;
+        }
+        let direction = tilt_to_direction(self.push.0, self.push.1);
+        self.push = (0, 0);
+        direction
+    }
+}
+
+impl Default for MomentumController {
+    fn default() -> Self {
+        MomentumController::new()
+    }
+}
+
+pub fn mag_to_direction(x: i32, y: i32) -> char {
+    // the way the top of the board is pointing, as a heading on the display with north as up.
+    // the field points north, so it runs along +y when the top faces north and along +x
+    // when the right hand side does, which leaves the top facing west
+    if x.abs() >= y.abs() {
+        if x > 0 { 'L' } else { 'R' }
+    } else {
+        if y > 0 { 'U' } else { 'D' }
+    }
+}
+
+pub fn accel_magnitude(x: i32, y: i32, z: i32) -> i32 {
+    // length of the acceleration vector in mg, rounded down. There's no float sqrt without std,
+    // so it's Newton's method on integers
+    let square = x as i64 * x as i64 + y as i64 * y as i64 + z as i64 * z as i64;
+    if square < 2 {
+        return square as i32;
+    }
+    let mut root = square;
+    let mut next = (root + square / root) / 2;
+    while next < root {
+        root = next;
+        next = (root + square / root) / 2;
+    }
+    root as i32
+}
+
+pub fn detect_shake(samples: &[i32]) -> bool {
+    // whether recent magnitudes swing far enough between their lowest and highest to be a shake
+    match (samples.iter().min(), samples.iter().max()) {
+        (Some(low), Some(high)) => high - low >= SHAKE_THRESHOLD,
+        _ => false,
+    }
+}
+
+pub fn rotate_direction(current: char, clockwise: bool) -> char {
+    // quarter turn from the current heading, as seen on the display
+    match (current, clockwise) {
+        ('R', true) | ('L', false) => 'D',
+        ('D', true) | ('U', false) => 'L',
+        ('L', true) | ('R', false) => 'U',
+        ('U', true) | ('D', false) => 'R',
+        _ => current,
+    }
+}
+
+// what steers the snake, picked on the start screen. Directions sent over serial steer in every mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlMode {
+    Serial,  // nothing but the keys sent over serial
+    Tilt,  // tilting the board
+    Momentum,  // tilting the board too, but it has to be held for a while to turn, see MomentumController
+    Buttons,  // button A turns counter-clockwise, B clockwise
+    Compass,  // pointing the top of the board like a compass
+}
+
+impl ControlMode {
+    // in the order the start screen shows them
+    pub const ALL: [ControlMode; 5] = [
+        ControlMode::Serial,
+        ControlMode::Tilt,
+        ControlMode::Momentum,
+        ControlMode::Buttons,
+        ControlMode::Compass,
+    ];
+
+    pub fn cycle(self, forwards: bool) -> ControlMode {
+        // the mode next to this one on the start screen, wrapping round at either end
+        let count = ControlMode::ALL.len();
+        let index = ControlMode::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        let step = if forwards { 1 } else { count - 1 };
+        ControlMode::ALL[(index + step) % count]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControlMode::Serial => "serial",
+            ControlMode::Tilt => "tilt",
+            ControlMode::Momentum => "momentum",
+            ControlMode::Buttons => "buttons",
+            ControlMode::Compass => "compass",
+        }
+    }
+}
+
+// where we are in an arrow key's escape sequence, ESC '[' then 'A' to 'D'
+pub enum KeyDecoderState {
+    Idle,
+    Escape,
+    Bracket,
+}
+
+pub fn decode_key(byte: u8, state: &mut KeyDecoderState) -> Option<char> {
+    // turns serial input into a direction: the raw letters, WASD, IJKL, or an arrow key split over three bytes
+    match state {
+        KeyDecoderState::Escape => {
+            *state = if byte == b'[' { KeyDecoderState::Bracket } else { KeyDecoderState::Idle };
+            None
+        }
+        KeyDecoderState::Bracket => {
+            *state = KeyDecoderState::Idle;
+            match byte {
+                b'A' => Some('U'),
+                b'B' => Some('D'),
+                b'C' => Some('R'),
+                b'D' => Some('L'),
+                _ => None,
+            }
+        }
+        KeyDecoderState::Idle => match byte {
+            0x1b => {
+                *state = KeyDecoderState::Escape;
+                None
+            }
+            b'R' | b'd' | b'l' => Some('R'),
+            b'L' | b'a' | b'j' => Some('L'),
+            b'U' | b'w' | b'i' => Some('U'),
+            b'D' | b's' | b'k' => Some('D'),
+            _ => None,
+        },
+    }
+}
+
+pub fn parse_digits(digits: &[u8]) -> Option<u32> {
+    // reads a buffered run of ascii digits as a number, None if it's empty, has anything else in it, or doesn't fit
+    if digits.is_empty() {
+        return None;
+    }
+    digits.iter().try_fold(0u32, |value, &byte| {
+        if !byte.is_ascii_digit() {
+            return None;
+        }
+        value.checked_mul(10)?.checked_add((byte - b'0') as u32)
+    })
+}
+
+pub fn second_player_key(byte: u8) -> Option<char> {
+    // in a two player game IJKL belong to the second snake, the first keeps WASD and the arrows
+    match byte {
+        b'l' => Some('R'),
+        b'j' => Some('L'),
+        b'i' => Some('U'),
+        b'k' => Some('D'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_board_does_not_steer() {
+        assert_eq!(tilt_to_direction(0, 0), None);
+        assert_eq!(tilt_to_direction(TILT_DEAD_ZONE - 1, -(TILT_DEAD_ZONE - 1)), None);
+    }
+
+    #[test]
+    fn strongest_tilt_wins() {
+        assert_eq!(tilt_to_direction(500, 100), Some('R'));
+        assert_eq!(tilt_to_direction(-500, 100), Some('L'));
+        assert_eq!(tilt_to_direction(100, 500), Some('D'));
+        assert_eq!(tilt_to_direction(100, -500), Some('U'));
+    }
+
+    #[test]
+    fn axes_can_be_inverted_and_swapped() {
+        // the board tilted towards raw +x
+        let cases = [
+            (TiltAxes::STANDARD, 'R'),
+            (TiltAxes { invert_x: true, ..TiltAxes::STANDARD }, 'L'),
+            (TiltAxes { swap: true, ..TiltAxes::STANDARD }, 'D'),
+            (TiltAxes { invert_x: true, swap: true, ..TiltAxes::STANDARD }, 'U'),
+        ];
+        for &(axes, expected) in cases.iter() {
+            assert_eq!(TiltController::with_axes(axes).update(600, 0), Some(expected), "{:?}", axes);
+        }
+        let upside_down = TiltAxes { invert_x: true, invert_y: true, ..TiltAxes::STANDARD };
+        assert_eq!(upside_down.apply(100, -200), (-100, 200));
+    }
+
+    #[test]
+    fn a_board_resting_on_a_slope_reads_as_level() {
+        // leaning 400mg to the right at rest isn't a tilt any more, only tilting past that is
+        let levelled = TiltAxes::STANDARD.with_offset((400, -50));
+        assert_eq!(levelled.apply(400, -50), (0, 0));
+        let mut tilt = TiltController::with_axes(levelled);
+        assert_eq!(tilt.update(400, -50), None);
+        assert_eq!(tilt.update(1000, -50), Some('R'));
+
+        // the offset comes off the raw reading, before the axes are turned round
+        let sideways = TiltAxes { invert_x: true, swap: true, ..TiltAxes::STANDARD }.with_offset((100, 0));
+        assert_eq!(sideways.apply(300, 50), (50, -200));
+    }
+
+    #[test]
+    fn tilt_near_the_diagonal_does_not_jitter() {
+        let mut tilt = TiltController::new();
+        assert_eq!(tilt.update(500, -100), Some('R'));
+        for _ in 0..5 {
+            assert_eq!(tilt.update(480, -500), Some('R'));
+            assert_eq!(tilt.update(500, -480), Some('R'));
+        }
+        // tilted clearly up, it turns
+        assert_eq!(tilt.update(100, -600), Some('U'));
+    }
+
+    #[test]
+    fn tilt_turns_wait_for_the_cooldown() {
+        let mut tilt = TiltController::new();
+        assert_eq!(tilt.update(600, 0), Some('R'));
+        assert_eq!(tilt.update(0, 600), Some('R'));
+        assert_eq!(tilt.update(0, 600), Some('D'));
+        assert_eq!(tilt.update(0, 0), None);
+    }
+
+    #[test]
+    fn momentum_turns_only_once_the_push_builds_up() {
+        let mut momentum = MomentumController::new();
+        assert_eq!(momentum.update(600, 0), None);
+        assert_eq!(momentum.update(600, 0), None);
+        assert_eq!(momentum.update(600, 0), None);
+        assert_eq!(momentum.update(600, 0), Some('R'));
+        // the push was used up by the turn
+        assert_eq!(momentum.update(0, -600), None);
+        assert_eq!(momentum.update(0, 0), None);
+    }
+
+    #[test]
+    fn momentum_lets_a_nudge_fade_away() {
+        let mut momentum = MomentumController::new();
+        assert_eq!(momentum.update(0, 1200), None);
+        for _ in 0..20 {
+            assert_eq!(momentum.update(0, 0), None);
+        }
+        // a wobble inside the dead zone never adds up to a turn
+        for _ in 0..20 {
+            assert_eq!(momentum.update(TILT_DEAD_ZONE - 1, 0), None);
+        }
+        assert_eq!(momentum.push, (0, 0));
+    }
+
+    #[test]
+    fn compass_buckets_into_four_headings() {
+        assert_eq!(mag_to_direction(10, 300), 'U');
+        assert_eq!(mag_to_direction(-10, -300), 'D');
+        assert_eq!(mag_to_direction(300, -10), 'L');
+        assert_eq!(mag_to_direction(-300, 10), 'R');
+    }
+
+    #[test]
+    fn keys_map_to_directions() {
+        let mut state = KeyDecoderState::Idle;
+        let decoded: Vec<Option<char>> = b"wasdR?".iter().map(|&byte| decode_key(byte, &mut state)).collect();
+        assert_eq!(decoded, [Some('U'), Some('L'), Some('D'), Some('R'), Some('R'), None]);
+    }
+
+    #[test]
+    fn second_player_keys_are_ijkl_only() {
+        let decoded: Vec<Option<char>> = b"ijklwR".iter().map(|&byte| second_player_key(byte)).collect();
+        assert_eq!(decoded, [Some('U'), Some('L'), Some('D'), Some('R'), None, None]);
+    }
+
+    #[test]
+    fn arrow_keys_are_reassembled() {
+        let mut state = KeyDecoderState::Idle;
+        let decoded: Vec<Option<char>> = b"\x1b[A\x1b[D\x1bxs".iter().map(|&byte| decode_key(byte, &mut state)).collect();
+        assert_eq!(decoded, [None, None, Some('U'), None, None, Some('L'), None, None, Some('D')]);
+    }
+
+    #[test]
+    fn digit_buffers_parse_to_numbers() {
+        assert_eq!(parse_digits(b"0"), Some(0));
+        assert_eq!(parse_digits(b"24301"), Some(24301));
+        assert_eq!(parse_digits(b"4294967295"), Some(u32::MAX));
+        assert_eq!(parse_digits(b""), None);
+        assert_eq!(parse_digits(b"12a"), None);
+        assert_eq!(parse_digits(b"4294967296"), None);
+    }
+
+    #[test]
+    fn magnitude_is_the_vector_length() {
+        assert_eq!(accel_magnitude(0, 0, -1000), 1000);
+        assert_eq!(accel_magnitude(300, 400, 0), 500);
+        assert_eq!(accel_magnitude(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn only_a_real_shake_counts() {
+        assert!(!detect_shake(&[]));
+        assert!(!detect_shake(&[1000, 1020, 985, 1010]));
+        assert!(!detect_shake(&[1000, 1150, 900, 1100]));
+        assert!(detect_shake(&[1000, 2100, 400, 1800]));
+    }
+
+    #[test]
+    fn control_modes_cycle_both_ways() {
+        let mut mode = ControlMode::Serial;
+        for _ in 0..ControlMode::ALL.len() {
+            mode = mode.cycle(true);
+        }
+        assert_eq!(mode, ControlMode::Serial);
+        assert_eq!(ControlMode::Serial.cycle(true), ControlMode::Tilt);
+        assert_eq!(ControlMode::Serial.cycle(false), ControlMode::Compass);
+        assert_eq!(ControlMode::Compass.cycle(true), ControlMode::Serial);
+    }
+
+    #[test]
+    fn four_rotations_come_back_around() {
+        for &start in ['R', 'D', 'L', 'U'].iter() {
+            for &clockwise in [true, false].iter() {
+                let mut direction = start;
+                for _ in 0..4 {
+                    direction = rotate_direction(direction, clockwise);
+                }
+                assert_eq!(direction, start);
+            }
+        }
+        assert_eq!(rotate_direction('R', true), 'D');
+        assert_eq!(rotate_direction('R', false), 'U');
+    }
+}
@@ -0,0 +1,254 @@
+use heapless::Vec;
+
+// width of the display, and of every glyph
+const GLYPH_WIDTH: usize = 5;
+// most digits a u32 can have
+const MAX_DIGITS: usize = 10;
+
+// 0 to 9, three columns wide in the middle of the 5x5 so digits side by side have a gap
+pub const DIGITS: [[[u8; 5]; 5]; 10] = [
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 0, 1, 0, 0],
+        [0, 1, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 0, 0, 1, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+];
+
+// the letters the end of a game spells out, in the same three middle columns as the digits
+const LETTERS: [(u8, [[u8; 5]; 5]); 7] = [
+    (b'A', [
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+    ]),
+    (b'E', [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+    ]),
+    (b'G', [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ]),
+    (b'M', [
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+    ]),
+    // rounded off, so it doesn't read as a 0
+    (b'O', [
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+    ]),
+    (b'R', [
+        [0, 1, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+    ]),
+    (b'V', [
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+    ]),
+];
+
+fn glyph(character: u8) -> [[u8; 5]; 5] {
+    // the pattern for a digit or one of the letters, blank for a space or anything there's no glyph for
+    match character {
+        b'0'..=b'9' => DIGITS[(character - b'0') as usize],
+        _ => LETTERS.iter().find(|(letter, _)| *letter == character).map_or([[0; 5]; 5], |(_, glyph)| *glyph),
+    }
+}
+
+fn digits(n: u32) -> Vec<u8, MAX_DIGITS> {
+    // the decimal digits of n, most significant first. Zero is a single 0
+    let mut digits = Vec::new();
+    let mut rest = n;
+    loop {
+        digits.push((rest % 10) as u8).ok();
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    digits
+}
+
+pub fn scroll_frames(n: u32) -> usize {
+    // how many one column steps it takes for n to scroll all the way in from the right and out to the left
+    GLYPH_WIDTH * (digits(n).len() + 1)
+}
+
+pub fn number_frame(n: u32, offset: usize) -> [[u8; 5]; 5] {
+    // the display offset columns into n scrolling right to left, see text_frame
+    let text: Vec<u8, MAX_DIGITS> = digits(n).iter().map(|digit| digit + b'0').collect();
+    text_frame(&text, offset)
+}
+
+pub fn text_scroll_frames(text: &[u8]) -> usize {
+    // the same as scroll_frames, for text
+    GLYPH_WIDTH * (text.len() + 1)
+}
+
+pub fn text_frame(text: &[u8], offset: usize) -> [[u8; 5]; 5] {
+    /*
+    The display offset columns into text scrolling right to left, as an on/off pattern.
+    The glyphs sit side by side on a strip that starts with a blank screen's worth of columns,
+    so offset 0 is blank, offset 5 shows the first one whole, and it keeps going until
+    text_scroll_frames(text), when the last one has gone off the left.
+    */
+    let mut frame = [[0; 5]; 5];
+    for column in 0..GLYPH_WIDTH {
+        let strip_column = match (offset + column).checked_sub(GLYPH_WIDTH) {
+            Some(strip_column) => strip_column,
+            None => continue,
+        };
+        let glyph = match text.get(strip_column / GLYPH_WIDTH) {
+            Some(&character) => glyph(character),
+            None => continue,
+        };
+        for (row, line) in frame.iter_mut().enumerate() {
+            line[column] = glyph[row][strip_column % GLYPH_WIDTH];
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_numbers_into_digits() {
+        assert_eq!(digits(0).as_slice(), [0]);
+        assert_eq!(digits(7).as_slice(), [7]);
+        assert_eq!(digits(305).as_slice(), [3, 0, 5]);
+        assert_eq!(digits(u32::MAX).len(), MAX_DIGITS);
+        assert_eq!(scroll_frames(7), 10);
+        assert_eq!(scroll_frames(42), 15);
+    }
+
+    #[test]
+    fn number_scrolls_in_from_the_right() {
+        // blank to start with, the first digit whole five columns in, then the next one coming on
+        assert_eq!(number_frame(42, 0), [[0; 5]; 5]);
+        assert_eq!(number_frame(42, 5), DIGITS[4]);
+        assert_eq!(number_frame(42, 10), DIGITS[2]);
+
+        // one column in, the leftmost column of the 4 shows up on the right edge
+        let frame = number_frame(42, 1);
+        for row in 0..5 {
+            assert_eq!(frame[row], [0, 0, 0, 0, DIGITS[4][row][0]]);
+        }
+
+        // halfway between the two, the right of the 4 and the left of the 2 share the screen
+        let frame = number_frame(42, 7);
+        for row in 0..5 {
+            assert_eq!(frame[row][..3], DIGITS[4][row][2..]);
+            assert_eq!(frame[row][3..], DIGITS[2][row][..2]);
+        }
+
+        // and once it's scrolled all the way through the screen is blank again
+        assert_eq!(number_frame(42, scroll_frames(42)), [[0; 5]; 5]);
+    }
+
+    #[test]
+    fn text_scrolls_letters_and_digits_alike() {
+        let text = b"GAME OVER 42";
+        assert_eq!(text_scroll_frames(text), GLYPH_WIDTH * 13);
+        assert_eq!(text_frame(text, 5), glyph(b'G'));
+        assert_eq!(text_frame(text, 25), [[0; 5]; 5]);
+        assert_eq!(text_frame(text, 55), DIGITS[4]);
+        assert_eq!(text_frame(text, 60), DIGITS[2]);
+        assert_eq!(text_frame(text, text_scroll_frames(text)), [[0; 5]; 5]);
+
+        // every letter it needs has a glyph, and the O can be told from a 0
+        for &character in b"GAMEOVER".iter() {
+            assert_ne!(glyph(character), [[0; 5]; 5], "{}", character as char);
+        }
+        assert_ne!(glyph(b'O'), glyph(b'0'));
+        assert_eq!(glyph(b'?'), [[0; 5]; 5]);
+    }
+}
@@ -0,0 +1,3930 @@
+use heapless::{Deque, Vec};
+use rtt_target::rprintln;
+
+// width and height of the board. The micro:bit matrix itself is 5x5, so other
+// sizes only make sense for the game logic, e.g. on a simulated display.
+pub const BOARD_SIZE: usize = 5;
+// one segment per cell of the board
+pub const SNAKE_CAPACITY: usize = BOARD_SIZE * BOARD_SIZE;
+// how many turns a snake can have between its head and its tail. A checkpoint is laid
+// on the head's cell at most once and is forgotten once the tail has been through that
+// cell, so there's never more than one per segment, and every segment shares the one list
+const CHECKPOINT_CAPACITY: usize = SNAKE_CAPACITY;
+
+// which edges of the board wrap round to the opposite one, indexed by the EDGE_ constants.
+// an edge that doesn't wrap is a wall, and running off the board through it ends the game
+pub const EDGE_TOP: usize = 0;
+pub const EDGE_BOTTOM: usize = 1;
+pub const EDGE_LEFT: usize = 2;
+pub const EDGE_RIGHT: usize = 3;
+pub const WRAP_ALL: [bool; 4] = [true; 4];
+pub const WRAP_NONE: [bool; 4] = [false; 4];
+
+// how many times a single nugget can be moved before it has to be eaten
+const MAX_REROLLS_PER_NUGGET: u8 = 2;
+// points taken off the score for each reroll
+const REROLL_PENALTY: u32 = 1;
+
+// brightness of empty cells while the display is inverted. Every other level is turned
+// round the same way, so cells that differ in brightness still differ once inverted
+const INVERTED_FIELD_LEVEL: u8 = 9;
+
+// slow cells are drawn dimmer than the snake
+const SLOW_CELL_LEVEL: u8 = 3;
+// brightness of the snake, the head stands out so you can tell which way it's going
+const HEAD_LEVEL: u8 = 9;
+const BODY_LEVEL: u8 = 5;
+const _: () = assert!(HEAD_LEVEL > BODY_LEVEL, "the head has to stand out from the body");
+// a turn that's been queued blinks the head out until the next tick, so the player can see it was taken.
+// the head is already as bright as the display goes, so going dark is the only flash that shows
+const TURN_FLASH_LEVEL: u8 = 0;
+const TURN_FLASH_TICKS: u8 = 1;
+// the cell the head ran into the body on flashes between this and dark once the game is over
+const CRASH_LEVEL: u8 = 9;
+// the body fades from BODY_LEVEL just behind the head down to this at the tail
+const TAIL_LEVEL: u8 = 2;
+// the second player's snake is drawn dimmer, so the two can be told apart
+const RIVAL_HEAD_LEVEL: u8 = 6;
+const RIVAL_BODY_LEVEL: u8 = 1;
+// obstacles start showing up after this many nuggets, and another one after each further batch
+const NUGGETS_PER_OBSTACLE: u32 = 3;
+const MAX_OBSTACLES: usize = 6;
+// obstacles are the dimmest thing on the board
+const OBSTACLE_LEVEL: u8 = 2;
+
+// one in this many eaten nuggets brings a speed nugget along, if there isn't one out already
+const SPEED_NUGGET_CHANCE: u8 = 4;
+// how many ticks the game runs at double speed after eating a speed nugget
+const BOOST_TICKS: u8 = 20;
+// how many ticks a dash moves the snake two cells instead of one
+const DASH_TICKS: u8 = 3;
+// ticks from the start of a dash before the next one can start, so it can't be held on all game
+const DASH_COOLDOWN_TICKS: u8 = 15;
+// the speed nugget blinks out of step with the normal one, at full brightness
+const SPEED_NUGGET_LEVEL: u8 = 9;
+
+// how many turns can be lined up between two ticks
+const TURN_QUEUE_CAPACITY: usize = 3;
+
+// brightness of the nugget
+const NUGGET_LEVEL: u8 = 7;
+
+// ticks a nugget can go uneaten before it moves somewhere else
+const NUGGET_TIMEOUT_TICKS: u32 = 40;
+
+// brightness of the empty cell in front of the head, hinting which way the snake is going
+const HEADING_HINT_LEVEL: u8 = 1;
+
+// ticks a cell the tail has just left stays faintly lit for, dimming a level each tick
+const TRAIL_TICKS: u8 = 2;
+
+// brightness of the time attack's clock, round the edge of the board under everything else
+const CLOCK_LEVEL: u8 = 1;
+// how many cells go round the edge of the board
+const EDGE_CELLS: usize = 4 * (BOARD_SIZE - 1);
+
+// ticks after growing in which the head can run into the newest tail segment without dying
+const GROWTH_GRACE_TICKS: u8 = 1;
+
+// tick delay for a freshly hatched two segment snake
+const BASE_TICK_MS: u32 = 500;
+// how much faster each extra segment makes the game
+const TICK_STEP_MS: u32 = 25;
+// the game never gets faster than this, so it stays playable
+const MIN_TICK_MS: u32 = 120;
+// metronome click pitch at the starting speed, how much higher each speed level makes it, and the highest it goes
+const METRONOME_BASE_HZ: u32 = 800;
+const METRONOME_STEP_HZ: u32 = 80;
+const METRONOME_MAX_HZ: u32 = 2000;
+// longest a metronome click lasts, and the most of a tick it can take up, one part in this many
+const METRONOME_CLICK_MS: u32 = 6;
+const METRONOME_TICK_SHARE: u32 = 10;
+// player speed settings run from 1 (slowest) to 9 (fastest), the middle one leaves the curve as it is
+pub const MAX_SPEED_SETTING: u8 = 9;
+const NEUTRAL_SPEED_SETTING: u8 = 5;
+// most segments a single nugget can be set to grow the snake by
+pub const MAX_GROWTH_PER_NUGGET: u8 = 5;
+// most nuggets that can be out at once, the one every game has and a few extras on top
+pub const MAX_NUGGETS: u8 = 4;
+const MAX_EXTRA_NUGGETS: usize = MAX_NUGGETS as usize - 1;
+// most slow cells a board can have
+const MAX_SLOW_CELLS: usize = 8;
+
+pub struct LcgRng {
+    // pseudorandom number generator, an xorshift32 these days despite the name
+    state: u32,
+}
+
+impl LcgRng {
+    pub fn new(seed: u32) -> Self {
+        // seed is generated from accelerometer data, see below for more.
+        // xorshift never leaves zero, so a zero seed is swapped for any other value
+        LcgRng { state: if seed == 0 { 0x9E37_79B9 } else { seed } }
+    }
+
+    fn next(&mut self) -> u32 {
+        // generates the next pseudorandom number
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    pub fn next_in_range(&mut self, min: u8, max: u8) -> u8 {
+        // scales the random number into bounds using its high bits, which avoids modulo bias.
+        // both bounds are inclusive, so 0 to 255 is a range of 256 and needs the wider type.
+        // swapped bounds are a bug in the caller, but outside debug builds they're just put back in order
+        debug_assert!(min <= max, "next_in_range called with min {} above max {}", min, max);
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        let range = (max - min) as u64 + 1;
+        ((self.next() as u64 * range) >> 32) as u8 + min
+    }
+}
+
+pub fn seed_from_accel(x: i32, y: i32, z: i32) -> u32 {
+    // folds all three axes together and scrambles the result, so the jitter in the
+    // low bits of any one axis changes the whole seed
+    let mut seed = (x as u32).wrapping_mul(0x9E37_79B1) ^ y as u32;
+    seed = seed.wrapping_mul(0x85EB_CA6B) ^ z as u32;
+    seed ^= seed >> 16;
+    seed = seed.wrapping_mul(0x7FEB_352D);
+    seed ^= seed >> 15;
+    seed = seed.wrapping_mul(0x846C_A68B);
+    seed ^ (seed >> 16)
+}
+
+pub struct Jungle {
+    // captures all the relevant parts of the game
+    snake: Snake,  // fairly obvious, represents snake
+    pub basemap: [[u8; BOARD_SIZE]; BOARD_SIZE],  // represents the underlying grid that will be displayed
+    trail: [[u8; BOARD_SIZE]; BOARD_SIZE],  // cells the tail left lately, each with the level it's fading from
+    vacated: [[u32; BOARD_SIZE]; BOARD_SIZE],  // the tick each cell was last left by the tail, plus one so 0 is never
+    previous_direction: char, 
+    pub nugget: (u8, u8),  // snake's target
+    extra_nuggets: Vec<(u8, u8), MAX_EXTRA_NUGGETS>,  // more nuggets out at the same time, each eaten like the main one
+    nugget_rerolls: u8,  // how many times the current nugget has been moved
+    rng: LcgRng,  // pseudorandom number generator
+    pub invert_display: bool,  // show the snake as a hole in a lit field
+    slow_cells: Vec<(u8, u8), MAX_SLOW_CELLS>,  // cells that hold the head back for a tick
+    waded: bool,  // the head has already spent its extra tick on the slow cell it's on
+    turned_at: Option<(i8, i8)>,  // cell the head last turned on, for as long as it's still sitting there
+    growth_grace: u8,  // ticks left in which the newest tail segment doesn't count as body
+    pub growth_per_nugget: u8,  // segments the snake grows by for every nugget
+    growth_pending: u8,  // segments still to grow, one is added a tick
+    pub game_over: bool,  // the snake ran into itself
+    crash_cell: Option<(i8, i8)>,  // where the head ran into its own body, if that's what ended the game
+    death_cause: Option<DeathCause>,  // what ended the game, None while it's on or once it's won
+    pub score: u32,  // nuggets eaten this game
+    nuggets_eaten: u32,  // the same, except rerolls don't take any off it
+    pub won: bool,  // the snake filled the whole board
+    pub wrap: [bool; 4],  // which edges wrap around, the others are walls, see EDGE_TOP
+    pub deadly_body: bool,  // running into its own body ends the game, otherwise the snake crosses over itself
+    pub reentry_gap: u32,  // ticks a cell the tail left has to stay empty before the head may go back onto it, 0 for none
+    pub body_collision: CollisionPolicy,  // what running into its own body does, while it's deadly
+    pub fixed_tick_ms: Option<u32>,  // a pace that doesn't pick up as the snake grows, instead of tick_delay_ms
+    pub starvation_ticks: Option<u32>,  // ticks the snake can go without eating before it starves, None to never starve
+    hungry_ticks: u32,  // ticks since the snake last ate
+    ticks: u32,  // how many times the game has been updated, the game's clock. Drives the nugget blinking
+    elapsed_ms: u32,  // game time, the tick delay of every tick played added up
+    pub paused: bool,  // the game is frozen, updates and the turns sent with them are ignored
+    turn_queue: Deque<char, TURN_QUEUE_CAPACITY>,  // turns waiting for their tick, one is taken per update
+    turn_flash_ticks: u8,  // ticks left of the head blinking for a turn that was just queued
+    obstacles: Vec<(i8, i8), MAX_OBSTACLES>,  // cells that end the game when the head runs into them
+    speed_nugget: Option<(u8, u8)>,  // power-up that speeds the game up for a while instead of growing the snake
+    boost_ticks_remaining: u8,  // how much longer the speed nugget's effect lasts
+    dash_ticks_remaining: u8,  // ticks left in which the snake moves two cells a tick
+    dash_cooldown: u8,  // ticks until another dash can be started
+    nugget_age: u32,  // ticks since the nugget was last placed by eating or timing out
+    speed_setting: u8,  // player's speed dial, scales the tick delay the snake's length gives
+    rival: Option<Snake>,  // second player's snake, sharing the board and the nuggets
+    rival_heading: char,  // the direction the second player's head is moving in
+    pub rival_score: u32,  // nuggets the second player has eaten this game
+    ticks_remaining: Option<u32>,  // ticks left in a time attack, None in an endless game
+    time_limit: u32,  // ticks a time attack started with, for the clock round the edge
+}
+
+// the jungle lives on main's stack, with a copy or two more in flight while a game is set up or restored,
+// on a board with 16 KB of RAM in all. This stops the build if it ever grows big enough to be a worry again
+const _: () = assert!(core::mem::size_of::<Jungle>() <= 2048, "Jungle has outgrown the stack");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    // something worth telling the player about, returned from update for the main loop to act on
+    Ate,  // the snake ate a nugget and grew
+    Died(DeathCause),  // the game is over, and why
+    Won,  // the snake filled the board
+    TimeUp,  // a time attack ran out of ticks, the game is over
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionPolicy {
+    // what happens when the head runs into the snake's own body
+    End,  // game over
+    Shrink,  // the snake loses the segment it ran into and everything behind it, and carries on
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeathCause {
+    // what ended a game that wasn't won
+    SelfCollision,  // a head ran into its own snake's body
+    Wall,  // a snake ran into an edge that doesn't wrap
+    Starvation,  // the snake went starvation_ticks without eating
+    Hazard,  // a head ran into an obstacle
+    Rival,  // the two snakes ran into each other
+    Timeout,  // a time attack ran out of ticks
+}
+
+// every cause in the order they're declared, for reading one back from a save state
+const DEATH_CAUSES: [DeathCause; 6] = [
+    DeathCause::SelfCollision,
+    DeathCause::Wall,
+    DeathCause::Starvation,
+    DeathCause::Hazard,
+    DeathCause::Rival,
+    DeathCause::Timeout,
+];
+
+impl DeathCause {
+    pub fn describe(self) -> &'static str {
+        // a few words for the summary at the end of a game
+        match self {
+            DeathCause::SelfCollision => "ran into itself",
+            DeathCause::Wall => "hit a wall",
+            DeathCause::Starvation => "starved",
+            DeathCause::Hazard => "hit an obstacle",
+            DeathCause::Rival => "ran into the other snake",
+            DeathCause::Timeout => "ran out of time",
+        }
+    }
+}
+
+pub enum NuggetPlacement {
+    // strategies for choosing where the next nugget goes
+    Random,  // any cell picked by the rng
+    FarFromHead,  // the free cell farthest from the head, going round the edges that wrap
+}
+
+impl Jungle {
+    pub fn new(snake: Snake, nugget: (u8, u8), rng: LcgRng) -> Self {
+        // initializes the jungle, moving the nugget out from under the snake if need be
+        let mut jungle = Self {
+            basemap: [[0; BOARD_SIZE]; BOARD_SIZE],
+            trail: [[0; BOARD_SIZE]; BOARD_SIZE],
+            vacated: [[0; BOARD_SIZE]; BOARD_SIZE],
+            snake,
+            previous_direction: 'R',
+            nugget,
+            extra_nuggets: Vec::new(),
+            nugget_rerolls: 0,
+            rng,
+            invert_display: false,
+            slow_cells: Vec::new(),
+            waded: false,
+            turned_at: None,
+            growth_grace: 0,
+            growth_per_nugget: 1,
+            growth_pending: 0,
+            game_over: false,
+            crash_cell: None,
+            death_cause: None,
+            score: 0,
+            nuggets_eaten: 0,
+            won: false,
+            wrap: WRAP_ALL,
+            deadly_body: true,
+            reentry_gap: 0,
+            body_collision: CollisionPolicy::End,
+            fixed_tick_ms: None,
+            starvation_ticks: None,
+            hungry_ticks: 0,
+            ticks: 0,
+            elapsed_ms: 0,
+            paused: false,
+            turn_queue: Deque::new(),
+            turn_flash_ticks: 0,
+            obstacles: Vec::new(),
+            speed_nugget: None,
+            boost_ticks_remaining: 0,
+            dash_ticks_remaining: 0,
+            dash_cooldown: 0,
+            nugget_age: 0,
+            speed_setting: NEUTRAL_SPEED_SETTING,
+            rival: None,
+            rival_heading: 'L',
+            rival_score: 0,
+            ticks_remaining: None,
+            time_limit: 0,
+        };
+        if jungle.occupied(nugget.0 as i8, nugget.1 as i8) {
+            jungle.place_nugget(NuggetPlacement::Random);
+        }
+        jungle.compose();
+        jungle
+    }
+
+    pub fn update(&mut self, new_direction: Option<char>) -> Option<GameEvent> {
+        // one tick of a single player game, see update_both
+        self.update_both(new_direction, None)
+    }
+
+    pub fn step(&mut self, input: Option<char>) -> Option<GameEvent> {
+        /*
+        Advances the game by exactly one tick, for running lots of them back to back, say in a simulator.
+        Nothing in an update waits or reads a clock: how long a tick lasts is only ever worked out
+        from the game's own state (tick_ms), and it's the caller that does the waiting, so any number
+        of steps in a loop play out the same as a game on the board would. Same as update
+        */
+        self.update(input)
+    }
+
+    pub fn update_both(&mut self, new_direction: Option<char>, rival_direction: Option<char>) -> Option<GameEvent> {
+        /*
+        Main driver of the game.
+        - iterate over each segment and update it, for the second player's snake too
+        - check to see if a head ran into a body, its own or the other snake's
+        - check to see if the nugget was eaten
+        - if the nugget was eaten, append the segment correctly & generate a new one
+        - change direction of the snake if this was indicated
+        - move the nugget if it has gone uneaten for too long
+        - compose the basemap from scratch, once everything has settled
+        - report what happened this tick, if anything
+        */
+        if self.game_over || self.won || self.paused {
+            return None;
+        }
+
+        // the direction passed in lines up behind anything already queued
+        if let Some(direction) = new_direction {
+            self.queue_direction(direction);
+        }
+
+        // the tick that just went by lasted as long as the snake's length and boost made it
+        self.elapsed_ms = self.elapsed_ms.saturating_add(self.tick_ms());
+
+        // the boost from a speed nugget wears off one tick at a time
+        self.boost_ticks_remaining = self.boost_ticks_remaining.saturating_sub(1);
+
+        // a turn's flash only lasts until the tick it was queued for
+        self.turn_flash_ticks = self.turn_flash_ticks.saturating_sub(1);
+
+        // the trail left behind the tail fades a step every tick
+        for cell in self.trail.iter_mut().flat_map(|row| row.iter_mut()) {
+            *cell = cell.saturating_sub(1);
+        }
+
+        let previous_scores = (self.score, self.rival_score);
+        let previous_eaten = self.nuggets_eaten;
+        // one turn per cell the head sits on. A head held back on a slow cell would otherwise
+        // take a second turn where it took the first, and the segments behind it only ever take
+        // the first checkpoint on a cell, so the second one waits in the queue for the next cell
+        let turned_here = self.turned_at.is_some() && self.turned_at == self.snake.segments.first().map(|head| head.point);
+        let next_direction = if turned_here { None } else { self.turn_queue.pop_front() };
+        self.advance(next_direction, rival_direction);
+
+        // a dashing snake makes a second move straight after, checked for crashes just like the first,
+        // so it can't jump over anything on the cell in between
+        self.dash_cooldown = self.dash_cooldown.saturating_sub(1);
+        if self.dash_ticks_remaining > 0 {
+            self.dash_ticks_remaining -= 1;
+            if !self.game_over && !self.won {
+                self.advance(None, None);
+            }
+        }
+
+        // a nugget nobody goes for moves on, so one stuck somewhere awkward doesn't stall the game
+        self.nugget_age += 1;
+        if !self.game_over && !self.won && self.nugget_age > NUGGET_TIMEOUT_TICKS && self.place_nugget(NuggetPlacement::Random) {
+            self.nugget_age = 0;
+            rprintln!("Nugget timed out, moved to: {}, {}", self.nugget.0, self.nugget.1);
+        }
+
+        // a snake left to go hungry for too long starves
+        if self.nuggets_eaten > previous_eaten {
+            self.hungry_ticks = 0;
+        } else {
+            self.hungry_ticks = self.hungry_ticks.saturating_add(1);
+        }
+        if !self.game_over && !self.won && self.starvation_ticks.is_some_and(|limit| self.hungry_ticks >= limit) {
+            rprintln!("Game over, starved after {} ticks without eating", self.hungry_ticks);
+            self.end(DeathCause::Starvation);
+        }
+
+        // a time attack ends when the clock runs out, whatever the score
+        if let Some(remaining) = self.ticks_remaining {
+            self.ticks_remaining = Some(remaining.saturating_sub(1));
+            if remaining <= 1 && !self.game_over && !self.won {
+                rprintln!("Time up, score: {}", self.score);
+                self.end(DeathCause::Timeout);
+            }
+        }
+
+        self.compose();
+        // the counters stop at the top rather than wrapping back to zero. It would take over a
+        // century of slow ticks to get there, and the nugget would stop blinking if it ever did
+        self.ticks = self.ticks.saturating_add(1);
+
+        // a body that's come apart shows up here, on the tick it happened
+        #[cfg(debug_assertions)]
+        self.assert_contiguous();
+
+        if self.death_cause == Some(DeathCause::Timeout) {
+            Some(GameEvent::TimeUp)
+        } else if let Some(cause) = self.death_cause {
+            Some(GameEvent::Died(cause))
+        } else if self.won {
+            Some(GameEvent::Won)
+        } else if self.score > previous_scores.0 || self.rival_score > previous_scores.1 {
+            Some(GameEvent::Ate)
+        } else {
+            None
+        }
+    }
+
+    fn advance(&mut self, new_direction: Option<char>, rival_direction: Option<char>) {
+        // moves the snake, and the second player's if there is one, one tick, see update
+        let optional_head = self.snake.segments.first().cloned();
+
+        // a grace tick given out by the last growth is used up by this one
+        let growth_grace = self.growth_grace > 0;
+        self.growth_grace = self.growth_grace.saturating_sub(1);
+
+        /*
+        Works out this tick's turn once, up front.
+        - only a real change of heading is a turn, repeats and anything that isn't a direction are ignored
+        - reversing straight into the body is ignored, the snake just keeps its heading
+        - a turn is only taken if the snake can remember it, otherwise the body would come apart.
+          One that doesn't fit goes back to the front of the queue and is tried again next tick
+        The turn is left as a checkpoint for every segment, and becomes the heading once the move sticks.
+        */
+        let heading = self.previous_direction;
+        let turn = new_direction.filter(|&direction| is_direction(direction) && direction != heading && !is_opposite(direction, heading));
+        let turn_fits = !self.snake.turns.is_full();
+        if let Some(direction) = turn.filter(|_| !turn_fits) {
+            rprintln!("Checkpoint list full, turn waits");
+            self.turn_queue.push_front(direction).ok();
+        }
+        let turn = turn.filter(|_| turn_fits);
+
+        if let Some(head) = optional_head {
+            // a head sitting on a slow cell stays put for one tick before moving on
+            let on_slow_cell = self.slow_cells.iter().any(|cell| head.point == (cell.0 as i8, cell.1 as i8));
+            let wading = on_slow_cell && !self.waded;
+            self.waded = wading;
+
+            // first work out where every segment goes, nothing is committed until the move turns out to be legal.
+            // the segments are worked out one at a time from the snake itself, rather than moving a copy of it
+            let moving = !wading;
+            let wrap = self.wrap;
+            let length = self.snake.segments.len();
+            if let Some(index) = (0..length).find(|&index| self.snake.next_point(index, turn, moving, wrap).is_none()) {
+                let point = self.snake.segments[index].point;
+                rprintln!("Game over, ran into the wall at: {}, {}", point.0, point.1);
+                self.end(DeathCause::Wall);
+                return;
+            }
+
+            // the second player moves alongside, it takes its turns straight away and doesn't wade
+            let rival_turn = self.rival.as_ref().and_then(|rival| {
+                let rival_fits = !rival.turns.is_full();
+                rival_direction.filter(|&direction| {
+                    rival_fits && is_direction(direction) && direction != self.rival_heading && !is_opposite(direction, self.rival_heading)
+                })
+            });
+            if let Some(rival) = self.rival.as_ref() {
+                if let Some(index) = (0..rival.segments.len()).find(|&index| rival.next_point(index, rival_turn, true, wrap).is_none()) {
+                    let point = rival.segments[index].point;
+                    rprintln!("Game over, player two ran into the wall at: {}, {}", point.0, point.1);
+                    self.end(DeathCause::Wall);
+                    return;
+                }
+            }
+
+            // then check the heads against where everything else ends up, so chasing a tail
+            // into the cell it's leaving this tick is safe
+            let new_head = self.snake.next_point(0, turn, moving, wrap).unwrap();
+            // right after growing, the new tail moves into the cell the old one is leaving,
+            // so it's left out of the check for the grace tick
+            let body_len = length.saturating_sub(1 + growth_grace as usize);
+            let bitten = (1..=body_len).position(|index| self.snake.next_point(index, turn, moving, wrap) == Some(new_head));
+            let mut kept = length;
+            match bitten.filter(|_| self.deadly_body) {
+                Some(_) if self.body_collision == CollisionPolicy::End => {
+                    rprintln!("Game over, ran into the body at: {}, {}", new_head.0, new_head.1);
+                    self.end(DeathCause::SelfCollision);
+                    self.crash_cell = Some(new_head);
+                    return;
+                }
+                Some(index) => {
+                    // a softer death: the bitten segment and the rest of the tail behind it drop off
+                    kept = index + 1;
+                    rprintln!("Bit the body at: {}, {}, length now {}", new_head.0, new_head.1, kept);
+                }
+                None => (),
+            }
+            // with a gap set, going back onto a cell the tail left too lately counts as running into the body,
+            // and the cell the tail is leaving this very tick has been empty for no time at all
+            let leaving = moving && length > 1 && self.snake.segments[length - 1].point == new_head;
+            let too_soon = leaving || self.ticks_since_vacated(new_head).is_some_and(|ticks| ticks < self.reentry_gap);
+            if self.deadly_body && self.reentry_gap > 0 && too_soon {
+                rprintln!("Game over, went back onto: {}, {} too soon", new_head.0, new_head.1);
+                self.end(DeathCause::SelfCollision);
+                self.crash_cell = Some(new_head);
+                return;
+            }
+            if self.obstacles.contains(&new_head) {
+                rprintln!("Game over, ran into an obstacle at: {}, {}", new_head.0, new_head.1);
+                self.end(DeathCause::Hazard);
+                return;
+            }
+            if let Some(rival) = self.rival.as_ref() {
+                let rival_length = rival.segments.len();
+                let rival_head = rival.next_point(0, rival_turn, true, wrap).unwrap();
+                let old_rival_head = rival.head().point;
+                // heads that swap cells have gone through each other just as much as heads that meet
+                if rival_head == new_head || (rival_head == head.point && new_head == old_rival_head) {
+                    rprintln!("Game over, the snakes ran into each other at: {}, {}", new_head.0, new_head.1);
+                    self.end(DeathCause::Rival);
+                    return;
+                }
+                if (0..rival_length).any(|index| rival.next_point(index, rival_turn, true, wrap) == Some(new_head)) {
+                    rprintln!("Game over, ran into player two at: {}, {}", new_head.0, new_head.1);
+                    self.end(DeathCause::Rival);
+                    return;
+                }
+                let rival_cause = if (1..rival_length).any(|index| rival.next_point(index, rival_turn, true, wrap) == Some(rival_head)) {
+                    Some(DeathCause::SelfCollision)
+                } else if (0..kept).any(|index| self.snake.next_point(index, turn, moving, wrap) == Some(rival_head)) {
+                    Some(DeathCause::Rival)
+                } else if self.obstacles.contains(&rival_head) {
+                    Some(DeathCause::Hazard)
+                } else {
+                    None
+                };
+                if let Some(cause) = rival_cause {
+                    rprintln!("Game over, player two crashed at: {}, {}", rival_head.0, rival_head.1);
+                    self.end(cause);
+                    return;
+                }
+            }
+
+            // the move is legal, so it sticks, and so does the turn.
+            // the cell the tail left starts fading out, unless something moved onto it
+            let old_tail = self.snake.segments.last().map(|segment| segment.point);
+            self.snake.segments.truncate(kept);
+            self.snake.take_move(turn, moving, wrap);
+            if let Some(cell) = old_tail.filter(|cell| !self.snake.occupies(cell.0, cell.1)) {
+                self.trail[cell.0 as usize][cell.1 as usize] = TRAIL_TICKS;
+                self.vacated[cell.0 as usize][cell.1 as usize] = self.ticks.saturating_add(1);
+            }
+            if let Some(direction) = turn {
+                self.previous_direction = direction;
+            }
+            self.turned_at = match turn {
+                Some(_) if wading => Some(head.point),
+                _ if wading => self.turned_at,
+                _ => None,
+            };
+            if let Some(rival) = self.rival.as_mut() {
+                rival.take_move(rival_turn, true, wrap);
+                self.rival_heading = rival_turn.unwrap_or(self.rival_heading);
+            }
+
+            // a speed nugget doesn't grow the snake, it only speeds the game up for a while
+            if self.speed_nugget == Some((new_head.0 as u8, new_head.1 as u8)) {
+                self.speed_nugget = None;
+                self.boost_ticks_remaining = BOOST_TICKS;
+                rprintln!("Speed boost for {} ticks", BOOST_TICKS);
+            }
+
+            // if the head has "eaten" a nugget, the snake has some growing to do
+            let eaten_extra = self.extra_nugget_at(new_head);
+            let ate = new_head == (self.nugget.0 as i8, self.nugget.1 as i8) || eaten_extra.is_some();
+            if ate {
+                self.growth_pending = self.growth_pending.saturating_add(self.growth_per_nugget);
+            }
+
+            // it grows a segment a tick into the cell the tail just left, until it's done
+            if self.growth_pending > 0 {
+                let segment = push_segment_to_back(self.snake.segments.last().unwrap());
+                rprintln!("New segment: {}, {}, {}", segment.point.0, segment.point.1, segment.default_direction);
+                if self.snake.add_segment(segment).is_err() {
+                    // can't happen while the capacity matches the board, but a full snake is a full board
+                    rprintln!("Snake is at capacity, you win!");
+                    self.won = true;
+                    return;
+                }
+                self.growth_pending -= 1;
+                self.growth_grace = GROWTH_GRACE_TICKS;
+            }
+
+            if ate {
+                self.score = self.score.saturating_add(1);
+                self.nuggets_eaten = self.nuggets_eaten.saturating_add(1);
+                rprintln!("Score: {}", self.score);
+
+                // a snake covering every cell has filled the board, and there's nowhere left for a nugget
+                if self.snake.segments.len() == SNAKE_CAPACITY || !self.replace_nugget(eaten_extra) {
+                    rprintln!("Board full, you win!");
+                    self.won = true;
+                    return;
+                }
+
+                // every so often a speed nugget shows up as well
+                if self.speed_nugget.is_none() && self.rng.next_in_range(1, SPEED_NUGGET_CHANCE) == 1 && self.place_speed_nugget() {
+                    let speed_nugget = self.speed_nugget.unwrap();
+                    rprintln!("New speed nugget: {}, {}", speed_nugget.0, speed_nugget.1);
+                }
+
+                // every few nuggets the jungle gets a little more crowded, however much each one grew the snake
+                if self.nuggets_eaten.is_multiple_of(NUGGETS_PER_OBSTACLE) && self.place_obstacle() {
+                    let obstacle = self.obstacles.last().unwrap();
+                    rprintln!("New obstacle: {}, {}", obstacle.0, obstacle.1);
+                }
+            }
+
+            // the second player goes for the same nuggets, and scores on its own
+            let rival_head = self.rival.as_ref().map(|rival| rival.head().point);
+            let rival_extra = rival_head.and_then(|head| self.extra_nugget_at(head));
+            let rival_ate = rival_head == Some((self.nugget.0 as i8, self.nugget.1 as i8)) || rival_extra.is_some();
+            if rival_ate {
+                let rival = self.rival.as_mut().unwrap();
+                let segment = push_segment_to_back(rival.segments.last().unwrap());
+                // the two snakes together can't outgrow the board, it fills up first
+                rival.add_segment(segment).ok();
+                self.rival_score = self.rival_score.saturating_add(1);
+                rprintln!("Player two score: {}", self.rival_score);
+
+                if !self.replace_nugget(rival_extra) {
+                    rprintln!("Board full, you win!");
+                    self.won = true;
+                }
+            }
+        }
+    }
+
+    fn compose(&mut self) {
+        // redraws the basemap from the current state: slow cells, then body, head and nugget on top
+        self.basemap = [[0; BOARD_SIZE]; BOARD_SIZE];
+
+        // a time attack's clock, the lit part of the edge shrinks as the ticks run out
+        if let Some(remaining) = self.ticks_remaining {
+            let lit = (EDGE_CELLS as u32 * remaining + self.time_limit - 1) / self.time_limit.max(1);
+            for index in 0..lit as usize {
+                paint(&mut self.basemap, edge_cell(index), CLOCK_LEVEL);
+            }
+        }
+
+        // the trail goes underneath everything that's really on the board
+        for (x, row) in self.trail.iter().enumerate() {
+            for (y, &level) in row.iter().enumerate() {
+                if level > 0 {
+                    paint(&mut self.basemap, (x as i8, y as i8), level);
+                }
+            }
+        }
+
+        for cell in self.slow_cells.iter() {
+            paint(&mut self.basemap, (cell.0 as i8, cell.1 as i8), SLOW_CELL_LEVEL);
+        }
+        for obstacle in self.obstacles.iter() {
+            paint(&mut self.basemap, *obstacle, OBSTACLE_LEVEL);
+        }
+        if let Some(rival) = self.rival.as_ref() {
+            for segment in rival.segments.iter().skip(1) {
+                paint(&mut self.basemap, segment.point, RIVAL_BODY_LEVEL);
+            }
+            paint(&mut self.basemap, rival.head().point, RIVAL_HEAD_LEVEL);
+        }
+        let length = self.snake.segments.len();
+        for (index, segment) in self.snake.segments.iter().enumerate().skip(1) {
+            paint(&mut self.basemap, segment.point, body_level(index, length));
+        }
+        if let Some(head) = self.snake.segments.first() {
+            paint_head(&mut self.basemap, head.point, self.previous_direction, self.wrap);
+            if self.turn_flash_ticks > 0 {
+                paint(&mut self.basemap, head.point, TURN_FLASH_LEVEL);
+            }
+        }
+
+        // the nugget blinks, showing up every other tick, so it can't be mistaken for the body.
+        // the speed nugget blinks on the other ticks, so the two are never lit together
+        if self.ticks.is_multiple_of(2) {
+            paint(&mut self.basemap, (self.nugget.0 as i8, self.nugget.1 as i8), NUGGET_LEVEL);
+            for nugget in self.extra_nuggets.iter() {
+                paint(&mut self.basemap, (nugget.0 as i8, nugget.1 as i8), NUGGET_LEVEL);
+            }
+        } else if let Some(speed_nugget) = self.speed_nugget {
+            paint(&mut self.basemap, (speed_nugget.0 as i8, speed_nugget.1 as i8), SPEED_NUGGET_LEVEL);
+        }
+    }
+
+    pub fn head(&self) -> (i8, i8) {
+        // where the head currently is
+        self.snake.head().point
+    }
+
+    pub fn tick_ms(&self) -> u32 {
+        /*
+        How long the next tick should last.
+        The snake's length and any boost give the delay, see tick_delay_ms, unless there's a fixed
+        pace, which a boost still halves. The speed setting scales it: setting s makes it
+        2 * NEUTRAL / (NEUTRAL + s) times as long, so about 1.7x at 1 and 0.7x at 9
+        */
+        let delay = match self.fixed_tick_ms {
+            Some(ms) if self.boosted() => ms / 2,
+            Some(ms) => ms,
+            None => tick_delay_ms(self.length(), self.boosted()),
+        };
+        delay * 2 * NEUTRAL_SPEED_SETTING as u32 / (NEUTRAL_SPEED_SETTING + self.speed_setting) as u32
+    }
+
+    pub fn speed_setting(&self) -> u8 {
+        self.speed_setting
+    }
+
+    pub fn set_speed_setting(&mut self, setting: u8) -> u8 {
+        // sets the speed dial, clamped to 1..=MAX_SPEED_SETTING, handing back what it ended up at
+        self.speed_setting = setting.clamp(1, MAX_SPEED_SETTING);
+        self.speed_setting
+    }
+
+    pub fn ticks(&self) -> u32 {
+        // how many ticks have been played, pauses and the end of the game don't count
+        self.ticks
+    }
+
+    pub fn elapsed_secs(&self) -> u32 {
+        // how long the game has been played for, going by the tick delay in force on each tick
+        self.elapsed_ms / 1000
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn assert_contiguous(&self) {
+        // debug builds only: panics unless every segment, on both snakes, is one cell from the
+        // one in front of it, going round the edges included
+        for snake in core::iter::once(&self.snake).chain(self.rival.as_ref()) {
+            for pair in snake.segments.windows(2) {
+                let (front, back) = (pair[0].point, pair[1].point);
+                let apart = wrapped_distance(front.0, back.0) + wrapped_distance(front.1, back.1);
+                assert!(apart == 1, "body broken between {:?} and {:?}", front, back);
+            }
+        }
+    }
+
+    pub fn heading(&self) -> char {
+        // the direction the head is currently moving in
+        self.previous_direction
+    }
+
+    pub fn intended_heading(&self) -> char {
+        // the direction the head will be moving in once every queued turn has been taken
+        *self.turn_queue.back().unwrap_or(&self.previous_direction)
+    }
+
+    pub fn queue_direction(&mut self, direction: char) -> bool {
+        // lines a turn up for a later tick, false if it was dropped. Repeats, reversals,
+        // anything that isn't a direction, and anything sent while paused are all dropped
+        let last = self.intended_heading();
+        if self.paused || !is_direction(direction) || direction == last || is_opposite(direction, last) {
+            return false;
+        }
+        if self.turn_queue.push_back(direction).is_err() {
+            return false;
+        }
+        // straight away, not on the next tick, so the flash shows as the turn goes in
+        self.turn_flash_ticks = TURN_FLASH_TICKS;
+        self.compose();
+        true
+    }
+
+    pub fn has_queued_turns(&self) -> bool {
+        !self.turn_queue.is_empty()
+    }
+
+    pub fn length(&self) -> usize {
+        // number of segments, head included
+        self.snake.segments.len()
+    }
+
+    pub fn snapshot(&self) -> GameState {
+        // what the board looks like right now, for tools on the host
+        let mut segments = [(0, 0); SNAKE_CAPACITY];
+        for (slot, segment) in segments.iter_mut().zip(self.snake.segments.iter()) {
+            *slot = segment.point;
+        }
+        GameState {
+            head: self.head(),
+            segments,
+            length: self.snake.segments.len() as u8,
+            nugget: self.nugget,
+            score: self.score,
+            game_over: self.game_over,
+            won: self.won,
+            paused: self.paused,
+        }
+    }
+
+    pub fn render(&self) -> [[u8; BOARD_SIZE]; BOARD_SIZE] {
+        // composes the frame that goes to the display from the basemap
+        let mut frame = self.basemap;
+        if self.invert_display {
+            // the brightest cells go dark and empty ones are lit, with everything in between flipped round
+            for row in frame.iter_mut() {
+                for cell in row.iter_mut() {
+                    *cell = INVERTED_FIELD_LEVEL - (*cell).min(INVERTED_FIELD_LEVEL);
+                }
+            }
+        }
+        frame
+    }
+
+    pub fn crash_cell(&self) -> Option<(i8, i8)> {
+        // where the head ran into its own body, once that's ended the game
+        self.crash_cell
+    }
+
+    pub fn death_cause(&self) -> Option<DeathCause> {
+        // what ended the game, None while it's still going or if it was won
+        self.death_cause
+    }
+
+    fn end(&mut self, cause: DeathCause) {
+        // game over, remembering why
+        self.game_over = true;
+        self.death_cause = Some(cause);
+    }
+
+    fn ticks_since_vacated(&self, cell: (i8, i8)) -> Option<u32> {
+        // how many ticks ago the tail left the cell, 0 if it was this one. None if it never has
+        match self.vacated[cell.0 as usize][cell.1 as usize] {
+            0 => None,
+            stamp => Some(self.ticks.saturating_add(1).saturating_sub(stamp)),
+        }
+    }
+
+    pub fn crash_frame(&self, frame: usize) -> [[u8; BOARD_SIZE]; BOARD_SIZE] {
+        // nth frame of showing where the snake ran into itself: the board as it ended,
+        // with the cell it hit flashing fully on and off
+        let mut board = self.render();
+        if let Some(cell) = self.crash_cell {
+            paint(&mut board, cell, if frame.is_multiple_of(2) { CRASH_LEVEL } else { 0 });
+        }
+        board
+    }
+
+    fn extra_nugget_at(&self, cell: (i8, i8)) -> Option<usize> {
+        // which of the extra nuggets is on the cell, if any
+        self.extra_nuggets.iter().position(|nugget| (nugget.0 as i8, nugget.1 as i8) == cell)
+    }
+
+    fn replace_nugget(&mut self, eaten_extra: Option<usize>) -> bool {
+        // puts an eaten nugget back somewhere else: the main one, or the extra one at the index.
+        // false if the main one has nowhere to go. An extra one with nowhere to go just stays gone
+        match eaten_extra {
+            Some(index) => {
+                self.extra_nuggets.swap_remove(index);
+                if self.place_extra_nugget() {
+                    let nugget = self.extra_nuggets.last().unwrap();
+                    rprintln!("New extra nugget: {}, {}", nugget.0, nugget.1);
+                }
+                true
+            }
+            None => {
+                if !self.place_nugget(NuggetPlacement::Random) {
+                    return false;
+                }
+                self.nugget_rerolls = 0;
+                self.nugget_age = 0;
+                rprintln!("New nugget: {}, {}", self.nugget.0, self.nugget.1);
+                true
+            }
+        }
+    }
+
+    fn place_extra_nugget(&mut self) -> bool {
+        // drops another nugget on a random cell a nugget could go on, false if there's no cell or no room for it
+        let nugget = (self.nugget.0 as i8, self.nugget.1 as i8);
+        let free_cells = (0..BOARD_SIZE as i8).flat_map(|x| (0..BOARD_SIZE as i8).map(move |y| (x, y)))
+            .filter(|&(x, y)| self.nugget_fits(x, y) && (x, y) != nugget)
+            .count();
+        if self.extra_nuggets.is_full() || free_cells == 0 {
+            return false;
+        }
+
+        loop {
+            let x = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+            let y = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+            if self.nugget_fits(x as i8, y as i8) && (x as i8, y as i8) != nugget {
+                return self.extra_nuggets.push((x, y)).is_ok();
+            }
+        }
+    }
+
+    pub fn nugget_count(&self) -> u8 {
+        // how many nuggets are out, the main one included
+        self.extra_nuggets.len() as u8 + 1
+    }
+
+    pub fn set_nugget_count(&mut self, count: u8) -> u8 {
+        // how many nuggets should be out at once, clamped to 1..=MAX_NUGGETS, handing back how many there are.
+        // that can come up short on a board too crowded to fit them all
+        let extras = count.clamp(1, MAX_NUGGETS) as usize - 1;
+        self.extra_nuggets.truncate(extras);
+        while self.extra_nuggets.len() < extras && self.place_extra_nugget() {}
+        self.compose();
+        self.nugget_count()
+    }
+
+    pub fn add_slow_cell(&mut self, x: u8, y: u8) -> bool {
+        // marks a cell as a slow zone, false if there's no room for another one
+        self.slow_cells.push((x, y)).is_ok()
+    }
+
+    pub fn place_nugget(&mut self, placement: NuggetPlacement) -> bool {
+        // moves the nugget to a free cell according to the given strategy, false if the board is full
+        let free_cells = (0..BOARD_SIZE as i8).flat_map(|x| (0..BOARD_SIZE as i8).map(move |y| (x, y)))
+            .filter(|&(x, y)| self.nugget_fits(x, y))
+            .count();
+        if free_cells == 0 {
+            return false;
+        }
+
+        match placement {
+            NuggetPlacement::Random => {
+                // keep rolling until the nugget lands somewhere the snake and obstacles aren't
+                loop {
+                    let x = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+                    let y = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+                    if self.nugget_fits(x as i8, y as i8) {
+                        self.nugget = (x, y);
+                        break;
+                    }
+                }
+            },
+            NuggetPlacement::FarFromHead => {
+                let head = self.snake.head().point;
+                let mut farthest = 0;
+                for x in 0..BOARD_SIZE as i8 {
+                    for y in 0..BOARD_SIZE as i8 {
+                        let distance = board_distance(head, (x, y), self.wrap);
+                        if self.nugget_fits(x, y) && distance > farthest {
+                            farthest = distance;
+                            self.nugget = (x as u8, y as u8);
+                        }
+                    }
+                }
+            },
+        }
+        true
+    }
+
+    fn occupied(&self, x: i8, y: i8) -> bool {
+        // whether any segment of either snake is on the cell
+        self.snake.occupies(x, y) || self.rival.as_ref().is_some_and(|rival| rival.occupies(x, y))
+    }
+
+    pub fn add_rival(&mut self, rival: Snake, heading: char) -> bool {
+        /*
+        Turns the game into a two player one, with a second snake steered through update_both.
+        False if the second snake would start on top of the first one or an obstacle.
+        */
+        if rival.segments.iter().any(|segment| self.blocked(segment.point.0, segment.point.1)) {
+            return false;
+        }
+        self.rival = Some(rival);
+        self.rival_heading = heading;
+        if self.occupied(self.nugget.0 as i8, self.nugget.1 as i8) {
+            self.place_nugget(NuggetPlacement::Random);
+        }
+        self.compose();
+        true
+    }
+
+    pub fn has_rival(&self) -> bool {
+        // whether there's a second snake on the board, which a resumed game brings back with it
+        self.rival.is_some()
+    }
+
+    pub fn start_time_attack(&mut self, ticks: u32) {
+        // gives the game a fixed number of ticks, after which it's over with whatever has been scored
+        self.ticks_remaining = Some(ticks.max(1));
+        self.time_limit = ticks.max(1);
+        self.compose();
+    }
+
+    pub fn ticks_remaining(&self) -> Option<u32> {
+        self.ticks_remaining
+    }
+
+    fn blocked(&self, x: i8, y: i8) -> bool {
+        // whether the snake or an obstacle is on the cell
+        self.occupied(x, y) || self.obstacles.contains(&(x, y))
+    }
+
+    fn bodies_fit(&self) -> bool {
+        /*
+        Whether the snakes are ones the game could have left behind, checked on restore.
+        - every body is in one piece, going round the edges included as wrap can be changed mid game
+        - no segment is on an obstacle
+        - the snakes are off each other, apart from a tail grown this tick. It sits on its own previous
+          point, the cell the old tail just left, which the other head may have moved onto
+        */
+        let just_grown = |snake: &Snake, index: usize| {
+            let segment = &snake.segments[index];
+            index == snake.segments.len() - 1 && segment.point == segment.prev_point
+        };
+        for snake in core::iter::once(&self.snake).chain(self.rival.as_ref()) {
+            let in_one_piece = snake.segments.windows(2).all(|pair| {
+                let (front, back) = (pair[0].point, pair[1].point);
+                wrapped_distance(front.0, back.0) + wrapped_distance(front.1, back.1) == 1
+            });
+            if !in_one_piece || snake.segments.iter().any(|segment| self.obstacles.contains(&segment.point)) {
+                return false;
+            }
+        }
+        let rival = match self.rival.as_ref() {
+            Some(rival) => rival,
+            None => return true,
+        };
+        (0..self.snake.segments.len()).all(|index| {
+            (0..rival.segments.len()).all(|rival_index| {
+                self.snake.segments[index].point != rival.segments[rival_index].point
+                    || just_grown(&self.snake, index)
+                    || just_grown(rival, rival_index)
+            })
+        })
+    }
+
+    fn nugget_fits(&self, x: i8, y: i8) -> bool {
+        // a nugget can go anywhere the snake, the obstacles, the speed nugget and the extra nuggets aren't
+        !self.blocked(x, y) && self.speed_nugget != Some((x as u8, y as u8)) && self.extra_nugget_at((x, y)).is_none()
+    }
+
+    fn place_speed_nugget(&mut self) -> bool {
+        // drops the speed nugget on a random cell a nugget could go on, false if there's none
+        let nugget = (self.nugget.0 as i8, self.nugget.1 as i8);
+        let free_cells = (0..BOARD_SIZE as i8).flat_map(|x| (0..BOARD_SIZE as i8).map(move |y| (x, y)))
+            .filter(|&(x, y)| self.nugget_fits(x, y) && (x, y) != nugget)
+            .count();
+        if free_cells == 0 {
+            return false;
+        }
+
+        loop {
+            let x = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+            let y = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+            if self.nugget_fits(x as i8, y as i8) && (x as i8, y as i8) != nugget {
+                self.speed_nugget = Some((x, y));
+                return true;
+            }
+        }
+    }
+
+    pub fn dash(&mut self) -> bool {
+        // starts a dash, two cells a tick for a few ticks. False while the last one is cooling down,
+        // or in a two player game, where the second player would get the extra moves too
+        if self.dash_cooldown > 0 || self.rival.is_some() || self.game_over || self.won || self.paused {
+            return false;
+        }
+        self.dash_ticks_remaining = DASH_TICKS;
+        self.dash_cooldown = DASH_COOLDOWN_TICKS;
+        true
+    }
+
+    pub fn boosted(&self) -> bool {
+        // whether a speed nugget's boost is still running
+        self.boost_ticks_remaining > 0
+    }
+
+    fn place_obstacle(&mut self) -> bool {
+        /*
+        Drops an obstacle on a random empty cell, false if there's no room or no cell left.
+        Slow cells, both nuggets and the cell right in front of the head are all kept clear,
+        so the snake never gets an obstacle it can't avoid.
+        */
+        let ahead = step(self.snake.head().point, self.previous_direction, self.wrap);
+        let nugget = (self.nugget.0 as i8, self.nugget.1 as i8);
+        let slow_cells = &self.slow_cells;
+        let candidate = |jungle: &Jungle, cell: (i8, i8)| {
+            jungle.nugget_fits(cell.0, cell.1) && cell != nugget && Some(cell) != ahead
+                && !slow_cells.iter().any(|slow| (slow.0 as i8, slow.1 as i8) == cell)
+        };
+
+        let free_cells = (0..BOARD_SIZE as i8).flat_map(|x| (0..BOARD_SIZE as i8).map(move |y| (x, y)))
+            .filter(|&cell| candidate(self, cell))
+            .count();
+        if self.obstacles.is_full() || free_cells == 0 {
+            return false;
+        }
+
+        loop {
+            let x = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1) as i8;
+            let y = self.rng.next_in_range(0, BOARD_SIZE as u8 - 1) as i8;
+            if candidate(self, (x, y)) {
+                return self.obstacles.push((x, y)).is_ok();
+            }
+        }
+    }
+
+    pub fn reroll_nugget(&mut self) -> bool {
+        // moves the nugget to the hardest spot to reach for a small score penalty, a limited number of times per nugget
+        if self.nugget_rerolls >= MAX_REROLLS_PER_NUGGET || !self.place_nugget(NuggetPlacement::FarFromHead) {
+            return false;
+        }
+        self.nugget_rerolls += 1;
+        self.score = self.score.saturating_sub(REROLL_PENALTY);
+        true
+    }
+
+    pub fn save_state(&self) -> StateBlob {
+        /*
+        Packs everything needed to resume the game into a fixed-size blob, so a resumed game plays on exactly
+        as this one would have. Only pausing is left out, a resumed game starts running.
+        - header: magic, format version, heading, nugget, rng state, flags and modes, edges that wrap, score
+        - the snake, then the second player's, with no segments if there isn't one. Each is a segment count,
+          one fixed-width record per segment (point, previous point, direction, checkpoints taken),
+          then a checkpoint count and the checkpoints
+        - the second player's heading and score
+        - obstacles, extra nuggets, slow cells and queued turns, each a count and then room for as many as there can be
+        - speed nugget, boost, dash, the per-nugget counters, growth, the speed dial and what ended the game
+        - the clocks, the time attack, the fixed pace, starving, the re-entry gap, and the turn and crash cells
+        - the fading trail, and when the tail last left each cell
+        - trailing checksum so a half-written or erased page is rejected
+        */
+        let mut bytes = [0; STATE_BLOB_LEN];
+        let mut writer = StateWriter { bytes: &mut bytes, offset: 0 };
+        writer.byte(STATE_MAGIC[0]);
+        writer.byte(STATE_MAGIC[1]);
+        writer.byte(STATE_VERSION);
+        writer.byte(self.previous_direction as u8);
+        writer.cell((self.nugget.0 as i8, self.nugget.1 as i8));
+        writer.word(self.rng.state);
+        writer.byte(self.game_over as u8 | (self.won as u8) << 1 | (self.deadly_body as u8) << 2
+            | ((self.body_collision == CollisionPolicy::Shrink) as u8) << 3
+            | (self.invert_display as u8) << 4 | (self.waded as u8) << 5);
+        writer.byte(self.wrap.iter().enumerate().fold(0, |bits, (edge, &wraps)| bits | (wraps as u8) << edge));
+        writer.word(self.score);
+
+        save_snake(&mut writer, &self.snake);
+        match &self.rival {
+            Some(rival) => save_snake(&mut writer, rival),
+            None => writer.skip(STATE_SNAKE_LEN),
+        }
+        writer.byte(self.rival_heading as u8);
+        writer.word(self.rival_score);
+
+        writer.byte(self.obstacles.len() as u8);
+        for &obstacle in self.obstacles.iter() {
+            writer.cell(obstacle);
+        }
+        writer.skip((MAX_OBSTACLES - self.obstacles.len()) * 2);
+        writer.byte(self.extra_nuggets.len() as u8);
+        for nugget in self.extra_nuggets.iter() {
+            writer.cell((nugget.0 as i8, nugget.1 as i8));
+        }
+        writer.skip((MAX_EXTRA_NUGGETS - self.extra_nuggets.len()) * 2);
+        writer.byte(self.slow_cells.len() as u8);
+        for cell in self.slow_cells.iter() {
+            writer.cell((cell.0 as i8, cell.1 as i8));
+        }
+        writer.skip((MAX_SLOW_CELLS - self.slow_cells.len()) * 2);
+        writer.byte(self.turn_queue.len() as u8);
+        for &turn in self.turn_queue.iter() {
+            writer.byte(turn as u8);
+        }
+        writer.skip(TURN_QUEUE_CAPACITY - self.turn_queue.len());
+
+        writer.optional_cell(self.speed_nugget.map(|(x, y)| (x as i8, y as i8)));
+        writer.byte(self.boost_ticks_remaining);
+        writer.byte(self.dash_ticks_remaining);
+        writer.byte(self.dash_cooldown);
+        writer.byte(self.turn_flash_ticks);
+        writer.byte(self.nugget_rerolls);
+        writer.byte(self.growth_per_nugget);
+        writer.byte(self.growth_pending);
+        writer.byte(self.growth_grace);
+        writer.byte(self.speed_setting);
+        writer.byte(self.death_cause.map_or(0, |cause| cause as u8 + 1));
+
+        writer.word(self.ticks);
+        writer.word(self.elapsed_ms);
+        writer.word(self.nugget_age);
+        writer.word(self.nuggets_eaten);
+        writer.word(self.time_limit);
+        writer.optional_word(self.ticks_remaining);
+        writer.optional_word(self.fixed_tick_ms);
+        writer.optional_word(self.starvation_ticks);
+        writer.word(self.hungry_ticks);
+        writer.word(self.reentry_gap);
+        writer.optional_cell(self.turned_at);
+        writer.optional_cell(self.crash_cell);
+
+        for row in self.trail.iter() {
+            for &level in row.iter() {
+                writer.byte(level);
+            }
+        }
+        for row in self.vacated.iter() {
+            for &stamp in row.iter() {
+                writer.word(stamp);
+            }
+        }
+        debug_assert_eq!(writer.offset, STATE_DATA_LEN, "save_state and the blob layout disagree");
+
+        bytes[STATE_BLOB_LEN - 1] = state_checksum(&bytes[..STATE_BLOB_LEN - 1]);
+        StateBlob { bytes }
+    }
+
+    pub fn restore(blob: &StateBlob) -> Result<Jungle, RestoreError> {
+        // rebuilds a jungle from a blob written by save_state, rejecting anything malformed
+        let bytes = &blob.bytes;
+        if bytes[0..2] != STATE_MAGIC {
+            return Err(RestoreError::BadMagic);
+        }
+        if bytes[2] != STATE_VERSION {
+            return Err(RestoreError::UnsupportedVersion(bytes[2]));
+        }
+        if bytes[STATE_BLOB_LEN - 1] != state_checksum(&bytes[..STATE_BLOB_LEN - 1]) {
+            return Err(RestoreError::BadChecksum);
+        }
+
+        let mut reader = StateReader { bytes, offset: 3 };
+        let previous_direction = reader.direction()?;
+        let nugget = reader.cell()?;
+        let rng_state = reader.word();
+        let flags = reader.byte();
+        let wrap_bits = reader.byte();
+        let score = reader.word();
+        let snake = restore_snake(&mut reader)?;
+        let rival = restore_snake(&mut reader)?;
+        if snake.segments.is_empty() {
+            return Err(RestoreError::Corrupt);
+        }
+
+        // new may move a nugget it finds under the snake, which a saved game never has, so the
+        // nugget and the rng go back in afterwards as they were saved
+        let mut jungle = Jungle::new(snake, (nugget.0 as u8, nugget.1 as u8), LcgRng::new(rng_state));
+        jungle.nugget = (nugget.0 as u8, nugget.1 as u8);
+        jungle.rng = LcgRng::new(rng_state);
+        jungle.previous_direction = previous_direction;
+        jungle.game_over = flags & 1 != 0;
+        jungle.won = flags & 2 != 0;
+        jungle.deadly_body = flags & 4 != 0;
+        jungle.body_collision = if flags & 8 != 0 { CollisionPolicy::Shrink } else { CollisionPolicy::End };
+        jungle.invert_display = flags & 16 != 0;
+        jungle.waded = flags & 32 != 0;
+        for (edge, wraps) in jungle.wrap.iter_mut().enumerate() {
+            *wraps = wrap_bits & 1 << edge != 0;
+        }
+        jungle.score = score;
+
+        if !rival.segments.is_empty() {
+            jungle.rival = Some(rival);
+        }
+        jungle.rival_heading = reader.direction()?;
+        jungle.rival_score = reader.word();
+
+        let count = reader.count(MAX_OBSTACLES)?;
+        for _ in 0..count {
+            jungle.obstacles.push(reader.cell()?).ok();
+        }
+        reader.skip((MAX_OBSTACLES - count) * 2);
+        let count = reader.count(MAX_EXTRA_NUGGETS)?;
+        for _ in 0..count {
+            let (x, y) = reader.cell()?;
+            jungle.extra_nuggets.push((x as u8, y as u8)).ok();
+        }
+        reader.skip((MAX_EXTRA_NUGGETS - count) * 2);
+        let count = reader.count(MAX_SLOW_CELLS)?;
+        for _ in 0..count {
+            let (x, y) = reader.cell()?;
+            jungle.slow_cells.push((x as u8, y as u8)).ok();
+        }
+        reader.skip((MAX_SLOW_CELLS - count) * 2);
+        let count = reader.count(TURN_QUEUE_CAPACITY)?;
+        for _ in 0..count {
+            jungle.turn_queue.push_back(reader.direction()?).ok();
+        }
+        reader.skip(TURN_QUEUE_CAPACITY - count);
+
+        jungle.speed_nugget = reader.optional_cell()?.map(|(x, y)| (x as u8, y as u8));
+        jungle.boost_ticks_remaining = reader.byte();
+        jungle.dash_ticks_remaining = reader.byte();
+        jungle.dash_cooldown = reader.byte();
+        jungle.turn_flash_ticks = reader.byte();
+        jungle.nugget_rerolls = reader.byte();
+        jungle.growth_per_nugget = reader.byte();
+        jungle.growth_pending = reader.byte();
+        jungle.growth_grace = reader.byte();
+        jungle.speed_setting = reader.byte();
+        if !(1..=MAX_SPEED_SETTING).contains(&jungle.speed_setting) {
+            return Err(RestoreError::Corrupt);
+        }
+        jungle.death_cause = match reader.byte() {
+            0 => None,
+            cause => Some(*DEATH_CAUSES.get(cause as usize - 1).ok_or(RestoreError::Corrupt)?),
+        };
+
+        jungle.ticks = reader.word();
+        jungle.elapsed_ms = reader.word();
+        jungle.nugget_age = reader.word();
+        jungle.nuggets_eaten = reader.word();
+        jungle.time_limit = reader.word();
+        jungle.ticks_remaining = reader.optional_word()?;
+        jungle.fixed_tick_ms = reader.optional_word()?;
+        jungle.starvation_ticks = reader.optional_word()?;
+        jungle.hungry_ticks = reader.word();
+        jungle.reentry_gap = reader.word();
+        jungle.turned_at = reader.optional_cell()?;
+        jungle.crash_cell = reader.optional_cell()?;
+
+        for row in jungle.trail.iter_mut() {
+            for level in row.iter_mut() {
+                *level = reader.byte();
+            }
+        }
+        for row in jungle.vacated.iter_mut() {
+            for stamp in row.iter_mut() {
+                *stamp = reader.word();
+                if *stamp > jungle.ticks {
+                    return Err(RestoreError::Corrupt);
+                }
+            }
+        }
+
+        // a clock or bodies the game can't get to would trip up compose and the movement code
+        let clock_fits = jungle.ticks_remaining.is_none_or(|remaining| jungle.time_limit > 0 && remaining <= jungle.time_limit);
+        if !clock_fits || !jungle.bodies_fit() {
+            return Err(RestoreError::Corrupt);
+        }
+
+        // only drawn once everything, the heading included, is back in place
+        jungle.compose();
+        Ok(jungle)
+    }
+}
+
+// save-state blob layout, see save_state. Bump STATE_VERSION whenever it changes
+const STATE_MAGIC: [u8; 2] = [b'S', b'N'];
+const STATE_VERSION: u8 = 10;
+const STATE_HEADER_LEN: usize = 16;
+const STATE_SEGMENT_LEN: usize = 6;
+const STATE_CHECKPOINT_LEN: usize = 3;
+// segment count and records, then checkpoint count and checkpoints
+const STATE_SNAKE_LEN: usize = 1 + SNAKE_CAPACITY * STATE_SEGMENT_LEN + 1 + CHECKPOINT_CAPACITY * STATE_CHECKPOINT_LEN;
+// the second player's heading and score
+const STATE_RIVAL_LEN: usize = 1 + 4;
+// obstacles, extra nuggets, slow cells and queued turns
+const STATE_LISTS_LEN: usize = 1 + MAX_OBSTACLES * 2 + 1 + MAX_EXTRA_NUGGETS * 2 + 1 + MAX_SLOW_CELLS * 2 + 1 + TURN_QUEUE_CAPACITY;
+// speed nugget, ten one-byte counters and settings, six clocks, the re-entry gap, three optional words and two optional cells
+const STATE_PROGRESS_LEN: usize = 2 + 10 + 7 * 4 + 3 * 5 + 2 * 2;
+// a byte a cell for the trail, then a word a cell for when each was last left
+const STATE_TRAIL_LEN: usize = BOARD_SIZE * BOARD_SIZE * 5;
+const STATE_DATA_LEN: usize =
+    STATE_HEADER_LEN + 2 * STATE_SNAKE_LEN + STATE_RIVAL_LEN + STATE_LISTS_LEN + STATE_PROGRESS_LEN + STATE_TRAIL_LEN;
+// the checksum after all that, padded to a whole number of flash words
+pub const STATE_BLOB_LEN: usize = (STATE_DATA_LEN + 1).div_ceil(4) * 4;
+
+pub struct StateBlob {
+    // fixed-size snapshot of a Jungle, small enough for a single flash page
+    bytes: [u8; STATE_BLOB_LEN],
+}
+
+impl StateBlob {
+    pub fn from_bytes(bytes: [u8; STATE_BLOB_LEN]) -> Self {
+        StateBlob { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameState {
+    /*
+    Read-only picture of a game at one tick, for a simulator or visualizer on the host.
+    Unlike StateBlob it can't resume a game, it only holds what's needed to draw one.
+    Segments past length are unused, head first.
+    */
+    pub head: (i8, i8),
+    pub segments: [(i8, i8); SNAKE_CAPACITY],
+    pub length: u8,
+    pub nugget: (u8, u8),
+    pub score: u32,
+    pub game_over: bool,
+    pub won: bool,
+    pub paused: bool,
+}
+
+// snapshot frame layout: start byte, flags, nugget, score, length, then one byte per segment.
+// cells are packed as row << 4 | column
+const GAME_STATE_FRAME_START: u8 = 0xA5;
+const GAME_STATE_HEADER_LEN: usize = 8;
+pub const GAME_STATE_FRAME_LEN: usize = GAME_STATE_HEADER_LEN + SNAKE_CAPACITY;
+
+impl GameState {
+    pub fn segments(&self) -> &[(i8, i8)] {
+        &self.segments[..self.length as usize]
+    }
+
+    pub fn encode(&self) -> Vec<u8, GAME_STATE_FRAME_LEN> {
+        // compact frame for the serial port, only as long as the snake
+        let mut frame = Vec::new();
+        frame.push(GAME_STATE_FRAME_START).ok();
+        frame.push(self.game_over as u8 | (self.won as u8) << 1 | (self.paused as u8) << 2).ok();
+        frame.push(self.nugget.0 << 4 | self.nugget.1).ok();
+        frame.extend_from_slice(&self.score.to_le_bytes()).ok();
+        frame.push(self.length).ok();
+        for point in self.segments() {
+            frame.push((point.0 as u8) << 4 | point.1 as u8).ok();
+        }
+        frame
+    }
+
+    pub fn decode(frame: &[u8]) -> Option<GameState> {
+        // the other way round, for the host. None if the frame is cut short or isn't one
+        if frame.len() < GAME_STATE_HEADER_LEN || frame[0] != GAME_STATE_FRAME_START {
+            return None;
+        }
+        let length = frame[7] as usize;
+        if length == 0 || length > SNAKE_CAPACITY || frame.len() < GAME_STATE_HEADER_LEN + length {
+            return None;
+        }
+
+        let mut segments = [(0, 0); SNAKE_CAPACITY];
+        for (slot, cell) in segments.iter_mut().zip(frame[GAME_STATE_HEADER_LEN..].iter().take(length)) {
+            *slot = ((cell >> 4) as i8, (cell & 0x0F) as i8);
+        }
+        let mut score = [0; 4];
+        score.copy_from_slice(&frame[3..7]);
+        Some(GameState {
+            head: segments[0],
+            segments,
+            length: length as u8,
+            nugget: (frame[2] >> 4, frame[2] & 0x0F),
+            score: u32::from_le_bytes(score),
+            game_over: frame[1] & 1 != 0,
+            won: frame[1] & 2 != 0,
+            paused: frame[1] & 4 != 0,
+        })
+    }
+}
+
+// how many finished games ScoreHistory remembers
+pub const SCORE_HISTORY_LEN: usize = 5;
+
+pub struct ScoreHistory {
+    // scores of the last few games this session, oldest first. Gone on power off
+    scores: Vec<u32, SCORE_HISTORY_LEN>,
+}
+
+impl ScoreHistory {
+    pub fn new() -> Self {
+        ScoreHistory { scores: Vec::new() }
+    }
+
+    pub fn push(&mut self, score: u32) {
+        // remembers a finished game, forgetting the oldest once it's full
+        if self.scores.is_full() {
+            self.scores.remove(0);
+        }
+        self.scores.push(score).ok();
+    }
+
+    pub fn dump(&self, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        // numbered list, most recent game first
+        if self.scores.is_empty() {
+            return out.write_str("No games yet\r\n");
+        }
+        for (place, score) in self.scores.iter().rev().enumerate() {
+            write!(out, "{}. {}\r\n", place + 1, score)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for ScoreHistory {
+    fn default() -> Self {
+        ScoreHistory::new()
+    }
+}
+
+#[derive(Debug)]
+pub enum RestoreError {
+    BadMagic,  // nothing was ever saved (erased flash reads as 0xFF)
+    UnsupportedVersion(u8),  // written by a different firmware
+    BadChecksum,  // torn or corrupted write
+    Corrupt,  // checksum matched but the contents make no sense
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CheckpointError {
+    BadDirection(char),  // not one of R, L, U, D
+    OffBoard(i8, i8),  // the cell isn't on the board
+    Full,  // no room left in the queue, the turn wasn't remembered
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SnakeFull;  // the snake already has a segment for every cell of the board, there's no room for another
+
+struct StateWriter<'a> {
+    // puts a save state together a field at a time, see save_state
+    bytes: &'a mut [u8],
+    offset: usize,
+}
+
+impl StateWriter<'_> {
+    fn byte(&mut self, byte: u8) {
+        self.bytes[self.offset] = byte;
+        self.offset += 1;
+    }
+
+    fn word(&mut self, word: u32) {
+        for byte in word.to_le_bytes() {
+            self.byte(byte);
+        }
+    }
+
+    fn cell(&mut self, cell: (i8, i8)) {
+        self.byte(cell.0 as u8);
+        self.byte(cell.1 as u8);
+    }
+
+    fn optional_cell(&mut self, cell: Option<(i8, i8)>) {
+        // 0xFF for both halves when there isn't one, which is never on the board
+        self.cell(cell.unwrap_or((-1, -1)));
+    }
+
+    fn optional_word(&mut self, word: Option<u32>) {
+        // whether there is one, then the word itself
+        self.byte(word.is_some() as u8);
+        self.word(word.unwrap_or(0));
+    }
+
+    fn skip(&mut self, len: usize) {
+        // leaves the rest of a list's room zeroed
+        self.offset += len;
+    }
+}
+
+struct StateReader<'a> {
+    // takes a save state apart in the same order StateWriter put it together, checking as it goes
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl StateReader<'_> {
+    fn byte(&mut self) -> u8 {
+        self.offset += 1;
+        self.bytes[self.offset - 1]
+    }
+
+    fn word(&mut self) -> u32 {
+        let mut word = [0; 4];
+        for byte in word.iter_mut() {
+            *byte = self.byte();
+        }
+        u32::from_le_bytes(word)
+    }
+
+    fn direction(&mut self) -> Result<char, RestoreError> {
+        let direction = self.byte() as char;
+        if !is_direction(direction) {
+            return Err(RestoreError::Corrupt);
+        }
+        Ok(direction)
+    }
+
+    fn count(&mut self, capacity: usize) -> Result<usize, RestoreError> {
+        // how many entries a list holds, which can't be more than it has room for
+        let count = self.byte() as usize;
+        if count > capacity {
+            return Err(RestoreError::Corrupt);
+        }
+        Ok(count)
+    }
+
+    fn cell(&mut self) -> Result<(i8, i8), RestoreError> {
+        let cell = (self.byte() as i8, self.byte() as i8);
+        if !in_bounds(cell.0, cell.1) {
+            return Err(RestoreError::Corrupt);
+        }
+        Ok(cell)
+    }
+
+    fn optional_cell(&mut self) -> Result<Option<(i8, i8)>, RestoreError> {
+        match (self.byte(), self.byte()) {
+            (0xFF, 0xFF) => Ok(None),
+            (x, y) if in_bounds(x as i8, y as i8) => Ok(Some((x as i8, y as i8))),
+            _ => Err(RestoreError::Corrupt),
+        }
+    }
+
+    fn optional_word(&mut self) -> Result<Option<u32>, RestoreError> {
+        match (self.byte(), self.word()) {
+            (0, _) => Ok(None),
+            (1, word) => Ok(Some(word)),
+            _ => Err(RestoreError::Corrupt),
+        }
+    }
+
+    fn skip(&mut self, len: usize) {
+        self.offset += len;
+    }
+}
+
+fn save_snake(writer: &mut StateWriter, snake: &Snake) {
+    // one snake's part of a save state, STATE_SNAKE_LEN bytes however long it is
+    writer.byte(snake.segments.len() as u8);
+    for segment in snake.segments.iter() {
+        writer.cell(segment.point);
+        writer.cell(segment.prev_point);
+        writer.byte(segment.default_direction as u8);
+        writer.byte(segment.turns_taken);
+    }
+    writer.skip((SNAKE_CAPACITY - snake.segments.len()) * STATE_SEGMENT_LEN);
+    writer.byte(snake.turns.len() as u8);
+    for &(x, y, direction) in snake.turns.iter() {
+        writer.cell((x, y));
+        writer.byte(direction as u8);
+    }
+    writer.skip((CHECKPOINT_CAPACITY - snake.turns.len()) * STATE_CHECKPOINT_LEN);
+}
+
+fn restore_snake(reader: &mut StateReader) -> Result<Snake, RestoreError> {
+    // the other way round, a snake with no segments if none was saved there
+    let mut snake = Snake { segments: Vec::new(), turns: Vec::new() };
+    let segment_count = reader.count(SNAKE_CAPACITY)?;
+    let mut most_taken = 0;
+    for _ in 0..segment_count {
+        let segment = Segment {
+            point: reader.cell()?,
+            prev_point: reader.cell()?,
+            default_direction: reader.direction()?,
+            turns_taken: reader.byte(),
+        };
+        most_taken = most_taken.max(segment.turns_taken as usize);
+        snake.add_segment(segment).ok();
+    }
+    reader.skip((SNAKE_CAPACITY - segment_count) * STATE_SEGMENT_LEN);
+
+    let checkpoint_count = reader.count(CHECKPOINT_CAPACITY)?;
+    if most_taken > checkpoint_count {
+        return Err(RestoreError::Corrupt);
+    }
+    for i in 0..checkpoint_count {
+        let (x, y) = reader.cell()?;
+        let added = snake.add_checkpoint(x, y, reader.direction()?);
+        if added.is_err() || snake.turns.len() != i + 1 {
+            return Err(RestoreError::Corrupt);
+        }
+    }
+    reader.skip((CHECKPOINT_CAPACITY - checkpoint_count) * STATE_CHECKPOINT_LEN);
+    Ok(snake)
+}
+
+fn state_checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum: u8, byte| sum.rotate_left(1) ^ byte)
+}
+
+fn in_bounds(x: i8, y: i8) -> bool {
+    x >= 0 && (x as usize) < BOARD_SIZE && y >= 0 && (y as usize) < BOARD_SIZE
+}
+
+fn paint_head(basemap: &mut [[u8; BOARD_SIZE]; BOARD_SIZE], head: (i8, i8), heading: char, wrap: [bool; 4]) {
+    // lights the head, and dimly the cell it's about to move into if nothing else is there,
+    // so it's easy to tell which way the snake is going. Nothing ahead of a head facing a wall
+    paint(basemap, head, HEAD_LEVEL);
+    if let Some(ahead) = step(head, heading, wrap) {
+        if basemap[ahead.0 as usize][ahead.1 as usize] == 0 {
+            paint(basemap, ahead, HEADING_HINT_LEVEL);
+        }
+    }
+}
+
+fn paint(basemap: &mut [[u8; BOARD_SIZE]; BOARD_SIZE], point: (i8, i8), level: u8) {
+    // lights a cell of the basemap. A point off the board is a bug somewhere else,
+    // but it's better to skip a cell than to index out of bounds and bring the board down
+    if !in_bounds(point.0, point.1) {
+        rprintln!("Skipped painting a cell off the board: {}, {}", point.0, point.1);
+        return;
+    }
+    basemap[point.0 as usize][point.1 as usize] = level;
+}
+
+fn body_level(index: usize, length: usize) -> u8 {
+    // brightness of the segment at index, head at 0, fading evenly towards the tail
+    // so the way the snake is heading can be read at a glance
+    if length <= 2 {
+        return BODY_LEVEL;
+    }
+    let fade = (index - 1) * (BODY_LEVEL - TAIL_LEVEL) as usize / (length - 2);
+    BODY_LEVEL - fade.min((BODY_LEVEL - TAIL_LEVEL) as usize) as u8
+}
+
+fn wrapped_distance(a: i8, b: i8) -> i8 {
+    // distance between two coordinates on an axis that wraps around
+    let distance = (a - b).abs();
+    let around = BOARD_SIZE as i8 - distance;
+    if distance > around { around } else { distance }
+}
+
+fn edge_cell(index: usize) -> (i8, i8) {
+    // the nth cell round the edge of the board, clockwise from the top left corner
+    let side = BOARD_SIZE - 1;
+    let offset = (index % side) as i8;
+    let last = side as i8;
+    match index / side {
+        0 => (0, offset),
+        1 => (offset, last),
+        2 => (last, last - offset),
+        _ => (last - offset, 0),
+    }
+}
+
+fn axis_distance(a: i8, b: i8, wraps: bool) -> i8 {
+    // distance between two coordinates on an axis, the short way round if it wraps
+    if wraps { wrapped_distance(a, b) } else { (a - b).abs() }
+}
+
+fn board_distance(a: (i8, i8), b: (i8, i8), wrap: [bool; 4]) -> i8 {
+    // moves between two cells, going round the edges that let the snake through.
+    // an axis only counts as going round if at least one of its edges does
+    let rows_wrap = wrap[EDGE_TOP] || wrap[EDGE_BOTTOM];
+    let columns_wrap = wrap[EDGE_LEFT] || wrap[EDGE_RIGHT];
+    axis_distance(a.0, b.0, rows_wrap) + axis_distance(a.1, b.1, columns_wrap)
+}
+
+fn step(point: (i8, i8), direction: char, wrap: [bool; 4]) -> Option<(i8, i8)> {
+    // the cell one move away in the given direction. Off the edge either wraps
+    // around to the other side, or is None when that edge is a wall
+    let (mut x, mut y) = point;
+    match direction {
+        'R' => y += 1,
+        'L' => y -= 1,
+        'U' => x -= 1,
+        'D' => x += 1,
+        _ => (),
+    }
+    if !in_bounds(x, y) {
+        let edge = if x < 0 {
+            EDGE_TOP
+        } else if x >= BOARD_SIZE as i8 {
+            EDGE_BOTTOM
+        } else if y < 0 {
+            EDGE_LEFT
+        } else {
+            EDGE_RIGHT
+        };
+        if !wrap[edge] {
+            return None;
+        }
+        x = x.rem_euclid(BOARD_SIZE as i8);
+        y = y.rem_euclid(BOARD_SIZE as i8);
+    }
+    Some((x, y))
+}
+
+pub fn apply_command(jungle: &mut Jungle, command: &str) -> bool {
+    /*
+    Changes a mode flag from a short text command, a letter and a number, e.g. "W1".
+    False if it isn't one of these or the number is out of range, leaving the jungle alone.
+    - W0 turns every edge into a wall, W1 lets every edge wrap
+    - D0 lets the snake cross over its own body, D1 makes running into it deadly again
+    - S1 to S9 sets the speed dial, see set_speed_setting
+    - G1 to G5 sets how many segments each nugget grows the snake by
+    - N1 to N4 sets how many nuggets are out at once
+    */
+    let command = command.trim();
+    let mut chars = command.chars();
+    let (letter, value) = match (chars.next(), chars.as_str().parse::<u8>()) {
+        (Some(letter), Ok(value)) => (letter.to_ascii_uppercase(), value),
+        _ => return false,
+    };
+    match (letter, value) {
+        ('W', 0) | ('W', 1) => jungle.wrap = if value == 1 { WRAP_ALL } else { WRAP_NONE },
+        ('D', 0) | ('D', 1) => jungle.deadly_body = value == 1,
+        ('S', 1..=MAX_SPEED_SETTING) => {
+            jungle.set_speed_setting(value);
+        }
+        ('G', 1..=MAX_GROWTH_PER_NUGGET) => jungle.growth_per_nugget = value,
+        ('N', 1..=MAX_NUGGETS) => {
+            jungle.set_nugget_count(value);
+        }
+        _ => return false,
+    }
+    true
+}
+
+pub fn dump_modes(jungle: &Jungle, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    // the mode flags written the way apply_command takes them, e.g. "W1 D1 S5 G1 N1".
+    // edges that don't all agree come out as W and a digit each for top, bottom, left and right
+    if jungle.wrap == WRAP_ALL || jungle.wrap == WRAP_NONE {
+        write!(out, "W{}", jungle.wrap[EDGE_TOP] as u8)?;
+    } else {
+        out.write_char('W')?;
+        for &wraps in jungle.wrap.iter() {
+            write!(out, "{}", wraps as u8)?;
+        }
+    }
+    write!(out, " D{} S{} G{} N{}", jungle.deadly_body as u8, jungle.speed_setting, jungle.growth_per_nugget, jungle.nugget_count())
+}
+
+pub fn autopilot_direction(jungle: &Jungle) -> char {
+    /*
+    Picks the next move for demo mode.
+    - never reverses, never runs into a wall, an obstacle or the body if there's any other way
+    - out of the safe moves, takes the one that gets closest to the nugget
+    - keeps going straight if there's nothing safe at all
+    */
+    let heading = jungle.previous_direction;
+    let head = match jungle.snake.segments.first() {
+        Some(segment) => segment.point,
+        None => return heading,
+    };
+    let nugget = (jungle.nugget.0 as i8, jungle.nugget.1 as i8);
+
+    let mut best = heading;
+    let mut best_distance = i8::MAX;
+    for &direction in ['U', 'R', 'D', 'L'].iter() {
+        if is_opposite(direction, heading) {
+            continue;
+        }
+        let next = match step(head, direction, jungle.wrap) {
+            Some(next) if !jungle.blocked(next.0, next.1) => next,
+            _ => continue,
+        };
+        let distance = board_distance(next, nugget, jungle.wrap);
+        if distance < best_distance {
+            best = direction;
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+pub fn render_ascii(jungle: &Jungle, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    /*
+    Writes the board out as five lines of text, for a plain serial terminal.
+    - 'O' head, '#' body, and 'o' and '+' for the second player's snake
+    - '*' nugget, any of them, whether or not it's blinked on, '$' speed nugget, 'X' obstacle
+    - '.' empty
+    */
+    let mut cells = [['.'; BOARD_SIZE]; BOARD_SIZE];
+    let mut mark = |point: (i8, i8), symbol: char| {
+        if in_bounds(point.0, point.1) {
+            cells[point.0 as usize][point.1 as usize] = symbol;
+        }
+    };
+    mark((jungle.nugget.0 as i8, jungle.nugget.1 as i8), '*');
+    for nugget in jungle.extra_nuggets.iter() {
+        mark((nugget.0 as i8, nugget.1 as i8), '*');
+    }
+    if let Some(speed_nugget) = jungle.speed_nugget {
+        mark((speed_nugget.0 as i8, speed_nugget.1 as i8), '$');
+    }
+    for obstacle in jungle.obstacles.iter() {
+        mark(*obstacle, 'X');
+    }
+    if let Some(rival) = jungle.rival.as_ref() {
+        for (i, segment) in rival.segments.iter().enumerate() {
+            mark(segment.point, if i == 0 { 'o' } else { '+' });
+        }
+    }
+    for (i, segment) in jungle.snake.segments.iter().enumerate() {
+        mark(segment.point, if i == 0 { 'O' } else { '#' });
+    }
+
+    for row in cells.iter() {
+        for cell in row.iter() {
+            out.write_char(*cell)?;
+        }
+        out.write_str("\r\n")?;
+    }
+    Ok(())
+}
+
+fn is_direction(c: char) -> bool {
+    c == 'R' || c == 'L' || c == 'U' || c == 'D'
+}
+
+pub fn tick_delay_ms(segment_count: usize, boosted: bool) -> u32 {
+    // the longer the snake, the shorter the tick, down to MIN_TICK_MS. A speed boost halves whatever it is
+    let extra_segments = segment_count.saturating_sub(2) as u32;
+    let delay = BASE_TICK_MS.saturating_sub(extra_segments.saturating_mul(TICK_STEP_MS)).max(MIN_TICK_MS);
+    if boosted { delay / 2 } else { delay }
+}
+
+pub fn speed_level(tick_ms: u32) -> u32 {
+    // 0 at the starting speed, one up for every step the tick has shrunk
+    (BASE_TICK_MS - tick_ms.min(BASE_TICK_MS)) / TICK_STEP_MS
+}
+
+pub fn metronome_hz(tick_ms: u32) -> u32 {
+    // pitch of the click on every tick, going up a step with every speed level
+    (METRONOME_BASE_HZ + speed_level(tick_ms) * METRONOME_STEP_HZ).min(METRONOME_MAX_HZ)
+}
+
+pub fn metronome_click_ms(tick_ms: u32) -> u32 {
+    // how long the click lasts. It blocks while it plays, so it's kept to a small part of even
+    // the shortest tick, and never drops to nothing
+    METRONOME_CLICK_MS.min(tick_ms / METRONOME_TICK_SHARE).max(1)
+}
+
+fn is_opposite(a: char, b: char) -> bool {
+    // true for the pairs of headings that would turn the snake back onto itself
+    matches!((a, b), ('R', 'L') | ('L', 'R') | ('U', 'D') | ('D', 'U'))
+}
+
+#[derive(Clone)]
+pub struct Snake {
+    // represents snake, which is composed of "Segments"
+    segments: Vec<Segment, SNAKE_CAPACITY>,
+    turns: Vec<(i8, i8, char), CHECKPOINT_CAPACITY>,  // checkpoints the head has laid that the tail hasn't taken yet, oldest first
+}
+
+impl Snake {
+    pub fn new() -> Self {
+        // initialize the snake with the head at (1, 1)
+        Snake::new_at((1, 1), 'R', 2).unwrap()
+    }
+
+    pub fn new_at(head: (i8, i8), heading: char, length: usize) -> Option<Self> {
+        // a straight snake with its head on the given cell, heading the given way with the body trailing behind.
+        // None for a heading that isn't R, L, U or D, or if the body would run off the board,
+        // since wrapping it round to the other side could lay it on top of itself
+        let behind = match heading {
+            'R' => 'L',
+            'L' => 'R',
+            'U' => 'D',
+            'D' => 'U',
+            _ => return None,
+        };
+        if length == 0 || length > SNAKE_CAPACITY || !in_bounds(head.0, head.1) {
+            return None;
+        }
+
+        let mut body = Vec::new();
+        let mut point = head;
+        for i in 0..length {
+            if i > 0 {
+                point = step(point, behind, WRAP_NONE)?;
+            }
+            body.push(Segment {
+                point,
+                prev_point: step(point, behind, WRAP_ALL).unwrap(),
+                default_direction: heading,
+                turns_taken: 0,
+            }).ok()?;
+        }
+
+        Some(Snake {
+            segments: body,
+            turns: Vec::new(),
+        })
+    }
+
+    pub fn with_length(length: usize) -> Option<Self> {
+        // a straight snake heading right along row 1, head first with the body trailing off to the left.
+        // None if it wouldn't fit in a single row, since it would wrap around into itself
+        if length == 0 || length > BOARD_SIZE {
+            return None;
+        }
+        Snake::new_at((1, length as i8 - 1), 'R', length)
+    }
+
+    pub fn rival_with_length(length: usize) -> Option<Self> {
+        // with_length turned halfway round: heading left along row 3, so a second snake starts clear of the first
+        let mut snake = Snake::with_length(length)?;
+        for segment in snake.segments.iter_mut() {
+            segment.point = (BOARD_SIZE as i8 - 1 - segment.point.0, BOARD_SIZE as i8 - 1 - segment.point.1);
+            segment.prev_point = (BOARD_SIZE as i8 - 1 - segment.prev_point.0, BOARD_SIZE as i8 - 1 - segment.prev_point.1);
+            segment.default_direction = 'L';
+        }
+        Some(snake)
+    }
+
+    pub fn add_segment(&mut self, segment: Segment) -> Result<(), SnakeFull> {
+        // append new segment to the snake, the segment is dropped if the snake is already full
+        self.segments.push(segment).map_err(|_| SnakeFull)
+    }
+
+    pub fn head(&self) -> &Segment {
+        // the snake always has at least its head
+        &self.segments[0]
+    }
+
+    pub fn occupies(&self, x: i8, y: i8) -> bool {
+        // whether any segment, head included, is on the cell
+        self.segments.iter().any(|segment| segment.point == (x, y))
+    }
+
+    pub fn add_checkpoint(&mut self, x: i8, y: i8, direction: char) -> Result<(), CheckpointError> {
+        /*
+        Used to add a new checkpoint for every segment to take once it gets to the cell.
+        - a turn that isn't R, L, U or D, or a cell off the board, is refused, either would
+          leave the segments with a checkpoint they can never take or a heading they can't move in
+        - the same turn twice in a row is only remembered once, it wouldn't change anything
+        - when the list is full the new turn is refused and the ones already laid are kept.
+          Dropping an older turn instead would send the body off the path the head took
+        */
+        if !is_direction(direction) {
+            return Err(CheckpointError::BadDirection(direction));
+        }
+        if !in_bounds(x, y) {
+            return Err(CheckpointError::OffBoard(x, y));
+        }
+        if self.turns.last() == Some(&(x, y, direction)) {
+            return Ok(());
+        }
+        self.turns.push((x, y, direction)).map_err(|_| CheckpointError::Full)
+    }
+
+    pub fn update(&mut self, wrap: [bool; 4]) -> bool {
+        // moves every segment a cell, each taking its next checkpoint if it's on it.
+        // false if a segment would leave the board through a wall, the ones behind it stay put
+        let mut ahead_taken = None;
+        for segment in self.segments.iter_mut() {
+            let taken = segment.turns_taken;
+            let checkpoint = self.turns.get(taken as usize).copied().filter(|_| checkpoint_due(taken, ahead_taken));
+            ahead_taken = Some(taken);
+            if !segment.update(checkpoint, wrap) {
+                return false;
+            }
+        }
+        self.forget_taken_turns();
+        true
+    }
+
+    fn next_point(&self, index: usize, turn: Option<char>, moving: bool, wrap: [bool; 4]) -> Option<(i8, i8)> {
+        /*
+        Where the segment at index will be after one tick, leaving the snake itself alone.
+        - a turn is left as a checkpoint on the head's cell for every segment to take there
+        - a snake that isn't moving this tick still takes the turn on board, but stays put
+        - None if the segment would leave the board through a wall. The body only ever
+          follows the head, so it's only the head that can
+        */
+        let mut segment = self.segments[index];
+        if !moving {
+            return Some(segment.point);
+        }
+        let head = self.head().point;
+        let ahead_taken = index.checked_sub(1).map(|ahead| self.segments[ahead].turns_taken);
+        let checkpoint = self.turns.get(segment.turns_taken as usize).copied()
+            .or_else(|| turn.map(|direction| (head.0, head.1, direction)))
+            .filter(|_| checkpoint_due(segment.turns_taken, ahead_taken));
+        if segment.update(checkpoint, wrap) { Some(segment.point) } else { None }
+    }
+
+    fn take_move(&mut self, turn: Option<char>, moving: bool, wrap: [bool; 4]) {
+        // makes the move next_point worked out, once it's turned out to be legal
+        let head = self.head().point;
+        if let Some(direction) = turn {
+            // the turn was checked before it got here, so this only goes wrong if that check did
+            if let Err(error) = self.add_checkpoint(head.0, head.1, direction) {
+                rprintln!("Turn not remembered: {:?}", error);
+            }
+        }
+        if moving {
+            self.update(wrap);
+        }
+        self.forget_taken_turns();
+    }
+
+    fn forget_taken_turns(&mut self) {
+        // drops the checkpoints every segment has been through, the tail is always the last to take one
+        let taken = self.segments.iter().map(|segment| segment.turns_taken).min().unwrap_or(0);
+        if taken == 0 {
+            return;
+        }
+        let remaining = self.turns.len() - taken as usize;
+        self.turns.rotate_left(taken as usize);
+        self.turns.truncate(remaining);
+        for segment in self.segments.iter_mut() {
+            segment.turns_taken -= taken;
+        }
+    }
+}
+
+impl Default for Snake {
+    fn default() -> Self {
+        Snake::new()
+    }
+}
+
+
+#[derive(Clone, Copy)]
+pub struct Segment {
+    /* 
+    Segment is the discrete element that makes up a snake.
+    - point indicates where the segment currently is
+    - prev point is where the segment was before its last update
+    - default direction indicates which way the segment should be moving
+    - turns taken counts how many of the snake's checkpoints the segment has been through
+    
+    Checkpoints are the secret sauce. This is how the snake "knows" when to
+    turn after the user has entered a turn. The head lays them, and the snake
+    keeps one list of them for every segment to work through in order.
+    */
+    point: (i8, i8),
+    prev_point: (i8, i8),
+    default_direction: char,
+    turns_taken: u8,
+}
+
+impl Segment {
+    pub fn update(&mut self, checkpoint: Option<(i8, i8, char)>, wrap: [bool; 4]) -> bool {
+        // update the segment based on its next checkpoint, false if it would leave the board through a wall
+        if let Some(value) = checkpoint.filter(|value| self.point.0 == value.0 && self.point.1 == value.1) {
+            self.default_direction = value.2;
+            self.turns_taken += 1;
+        }
+
+        // update point's location based on direction
+        match step(self.point, self.default_direction, wrap) {
+            Some(point) => {
+                self.prev_point = self.point;
+                self.point = point;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn checkpoint_due(taken: u8, ahead_taken: Option<u8>) -> bool {
+    // a segment can only take its next checkpoint once the segment in front of it has, the head
+    // any time. A body crossing over itself can be on a turn's cell well before it's due there
+    ahead_taken.is_none_or(|ahead| ahead > taken)
+}
+
+pub fn push_segment_to_back(last_segment: &Segment) -> Segment {
+    /*
+    Builds the segment that gets appended when the snake grows.
+    It takes the cell the last segment just left and moves the way the last
+    segment just moved, with the same checkpoints still ahead of it, so it
+    follows in lockstep from the next tick on.
+    */
+    Segment {
+        point: last_segment.prev_point,
+        prev_point: last_segment.prev_point,
+        default_direction: last_segment.default_direction,
+        turns_taken: last_segment.turns_taken,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(point: (i8, i8), prev_point: (i8, i8), direction: char) -> Segment {
+        Segment {
+            point,
+            prev_point,
+            default_direction: direction,
+            turns_taken: 0,
+        }
+    }
+
+    fn snake_of(segments: &[Segment]) -> Snake {
+        let mut snake = Snake { segments: Vec::new(), turns: Vec::new() };
+        for segment in segments {
+            assert!(snake.add_segment(*segment).is_ok());
+        }
+        snake
+    }
+
+    fn jungle_with(segments: &[Segment], nugget: (u8, u8)) -> Jungle {
+        Jungle::new(snake_of(segments), nugget, LcgRng::new(1))
+    }
+
+    fn pending(snake: &Snake, index: usize) -> &[(i8, i8, char)] {
+        // the checkpoints the segment at index still has to take
+        &snake.turns[snake.segments[index].turns_taken as usize..]
+    }
+
+    fn checkpoint_count(jungle: &Jungle) -> usize {
+        (0..jungle.snake.segments.len()).map(|index| pending(&jungle.snake, index).len()).sum()
+    }
+
+    fn points(jungle: &Jungle) -> std::vec::Vec<(i8, i8)> {
+        jungle.snake.segments.iter().map(|segment| segment.point).collect()
+    }
+
+    #[test]
+    fn rng_spreads_evenly_across_the_board() {
+        let mut rng = LcgRng::new(1);
+        let mut counts = [0; BOARD_SIZE];
+        for _ in 0..1000 {
+            counts[rng.next_in_range(0, BOARD_SIZE as u8 - 1) as usize] += 1;
+        }
+        for count in counts.iter() {
+            assert!(*count > 150 && *count < 250, "uneven spread: {:?}", counts);
+        }
+    }
+
+    #[test]
+    fn rng_range_bounds_are_inclusive() {
+        let mut rng = LcgRng::new(7);
+        for _ in 0..100 {
+            assert_eq!(rng.next_in_range(0, 0), 0);
+            assert!(rng.next_in_range(0, 4) <= 4);
+            assert_eq!(rng.next_in_range(9, 9), 9);
+        }
+        let full: std::vec::Vec<u8> = (0..1000).map(|_| rng.next_in_range(0, u8::MAX)).collect();
+        assert!(full.iter().any(|&value| value < 16) && full.iter().any(|&value| value > 240));
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic)]
+    fn rng_range_with_swapped_bounds() {
+        // caught in debug builds, quietly put back in order otherwise
+        let mut rng = LcgRng::new(7);
+        assert!(rng.next_in_range(4, 0) <= 4);
+    }
+
+    #[test]
+    fn every_axis_changes_the_seed() {
+        let seed = seed_from_accel(12, -40, 1010);
+        assert_ne!(seed_from_accel(13, -40, 1010), seed);
+        assert_ne!(seed_from_accel(12, -39, 1010), seed);
+        assert_ne!(seed_from_accel(12, -40, 1011), seed);
+    }
+
+    #[test]
+    fn moves_one_cell_per_tick() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 2), (1, 1)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 3), (1, 2)]);
+    }
+
+    #[test]
+    fn head_is_drawn_brighter_than_the_body() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.update(None);
+        assert_eq!(jungle.basemap[1][2], HEAD_LEVEL);
+        assert_eq!(jungle.basemap[1][1], BODY_LEVEL);
+        assert_eq!(jungle.basemap[4][4], NUGGET_LEVEL);
+    }
+
+    #[test]
+    fn inverting_shows_the_complement_of_every_level() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        let normal = jungle.render();
+        jungle.invert_display = true;
+        let inverted = jungle.render();
+        for (row, inverted_row) in normal.iter().zip(inverted.iter()) {
+            for (&level, &inverted_level) in row.iter().zip(inverted_row.iter()) {
+                assert_eq!(inverted_level, INVERTED_FIELD_LEVEL - level);
+            }
+        }
+        // the head is a hole, the nugget stays apart from the body, and the empty field is lit
+        assert_eq!(inverted[1][1], 0);
+        assert_eq!(inverted[1][0], INVERTED_FIELD_LEVEL - BODY_LEVEL);
+        assert_eq!(inverted[4][4], INVERTED_FIELD_LEVEL - NUGGET_LEVEL);
+        assert_ne!(inverted[4][4], inverted[1][0]);
+        assert_eq!(inverted[3][3], INVERTED_FIELD_LEVEL);
+        // and it's only the frame that's inverted, the game underneath is unchanged
+        assert_eq!(jungle.basemap, normal);
+    }
+
+    #[test]
+    fn the_tail_leaves_a_fading_trail() {
+        // with walls, so the heading hint doesn't wrap round onto the trail
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        jungle.update(None);
+        assert_eq!(jungle.basemap[1][0], TRAIL_TICKS);
+        jungle.update(None);
+        assert_eq!(jungle.basemap[1][0], TRAIL_TICKS - 1);
+        assert_eq!(jungle.basemap[1][1], TRAIL_TICKS);
+        jungle.update(None);
+        assert_eq!(jungle.basemap[1][0], 0);
+        assert_eq!(jungle.basemap[1][1], TRAIL_TICKS - 1);
+    }
+
+    #[test]
+    fn the_cell_ahead_of_the_head_is_hinted() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert_eq!(jungle.basemap[1][2], HEADING_HINT_LEVEL);
+        jungle.update(Some('D'));
+        assert_eq!(jungle.basemap[3][1], HEADING_HINT_LEVEL);
+        assert_eq!(jungle.basemap[1][2], 0);
+
+        // nothing is hinted through a wall, or over something already on the board
+        jungle.wrap = WRAP_NONE;
+        jungle.update(None);
+        jungle.update(None);
+        assert_eq!(jungle.head(), (4, 1));
+        assert!(jungle.basemap[0][1] != HEADING_HINT_LEVEL);
+        let mut basemap = [[0; BOARD_SIZE]; BOARD_SIZE];
+        basemap[2][3] = NUGGET_LEVEL;
+        paint_head(&mut basemap, (2, 2), 'R', WRAP_ALL);
+        assert_eq!(basemap[2][3], NUGGET_LEVEL);
+    }
+
+    #[test]
+    fn body_fades_towards_the_tail() {
+        let jungle = Jungle::new(Snake::with_length(5).unwrap(), (4, 4), LcgRng::new(1));
+        assert_eq!(jungle.basemap[1], [TAIL_LEVEL, 3, 4, BODY_LEVEL, HEAD_LEVEL]);
+    }
+
+    #[test]
+    fn nugget_blinks_every_other_tick() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.update(None);
+        assert_eq!(jungle.basemap[4][4], NUGGET_LEVEL);
+        jungle.basemap = [[0; BOARD_SIZE]; BOARD_SIZE];
+        jungle.update(None);
+        assert_eq!(jungle.basemap[4][4], 0);
+        jungle.update(None);
+        assert_eq!(jungle.basemap[4][4], NUGGET_LEVEL);
+    }
+
+    #[test]
+    fn paused_game_stays_put_and_ignores_turns() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.paused = true;
+        jungle.update(Some('D'));
+        assert_eq!(points(&jungle), [(1, 1), (1, 0)]);
+        jungle.paused = false;
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn cells_off_the_board_are_not_painted() {
+        let mut basemap = [[0; BOARD_SIZE]; BOARD_SIZE];
+        paint(&mut basemap, (-1, 2), HEAD_LEVEL);
+        paint(&mut basemap, (2, BOARD_SIZE as i8), HEAD_LEVEL);
+        assert_eq!(basemap, [[0; BOARD_SIZE]; BOARD_SIZE]);
+        paint(&mut basemap, (2, 3), HEAD_LEVEL);
+        assert_eq!(basemap[2][3], HEAD_LEVEL);
+    }
+
+    #[test]
+    fn wraps_around_the_right_edge() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for _ in 0..4 {
+            jungle.update(None);
+        }
+        assert_eq!(points(&jungle), [(1, 0), (1, 4)]);
+    }
+
+    #[test]
+    fn edges_can_wrap_or_wall_one_by_one() {
+        // the left and right edges are walls, the top and bottom still wrap
+        let mut jungle = Jungle::new(Snake::new(), (2, 3), LcgRng::new(1));
+        jungle.wrap = [true, true, false, false];
+        jungle.update(Some('U'));
+        jungle.update(None);
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.head(), (4, 1));
+        jungle.update(Some('R'));
+        jungle.update(None);
+        jungle.update(None);
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.head(), (4, 4));
+        jungle.update(None);
+        assert!(jungle.game_over);
+
+        // a wall on the top edge only
+        assert_eq!(step((0, 2), 'U', [false, true, true, true]), None);
+        assert_eq!(step((4, 2), 'D', [false, true, true, true]), Some((0, 2)));
+        assert_eq!(step((2, 0), 'L', [true, true, true, false]), Some((2, 4)));
+        assert_eq!(step((2, 4), 'R', [true, true, true, false]), None);
+    }
+
+    #[test]
+    fn wall_mode_ends_the_game_at_the_edge() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        for _ in 0..3 {
+            jungle.update(None);
+        }
+        assert!(!jungle.game_over);
+        jungle.update(None);
+        assert!(jungle.game_over);
+        assert_eq!(jungle.death_cause(), Some(DeathCause::Wall));
+        assert_eq!(points(&jungle), [(1, 4), (1, 3)]);
+    }
+
+    #[test]
+    fn segment_turns_only_on_its_checkpoint() {
+        let mut lone = snake_of(&[segment((1, 1), (1, 0), 'R')]);
+        lone.add_checkpoint(1, 2, 'D').unwrap();
+        lone.update(WRAP_ALL);
+        assert_eq!((lone.head().point, lone.head().default_direction), ((1, 2), 'R'));
+        assert_eq!(pending(&lone, 0).len(), 1);
+        lone.update(WRAP_ALL);
+        assert_eq!((lone.head().point, lone.head().default_direction), ((2, 2), 'D'));
+        assert!(pending(&lone, 0).is_empty());
+    }
+
+    #[test]
+    fn segment_keeps_going_when_the_checkpoint_is_off_its_path() {
+        let mut lone = snake_of(&[segment((0, 0), (0, 4), 'R')]);
+        lone.add_checkpoint(3, 3, 'D').unwrap();
+        for _ in 0..BOARD_SIZE {
+            lone.update(WRAP_ALL);
+        }
+        assert_eq!((lone.head().point, lone.head().default_direction), ((0, 0), 'R'));
+        assert_eq!(pending(&lone, 0).len(), 1);
+    }
+
+    #[test]
+    fn segment_takes_queued_checkpoints_in_order() {
+        let mut lone = snake_of(&[segment((1, 1), (1, 0), 'R')]);
+        lone.add_checkpoint(1, 2, 'D').unwrap();
+        lone.add_checkpoint(2, 2, 'L').unwrap();
+        lone.update(WRAP_ALL);
+        lone.update(WRAP_ALL);
+        assert_eq!((lone.head().point, lone.head().default_direction), ((2, 2), 'D'));
+        assert_eq!(pending(&lone, 0).len(), 1);
+        lone.update(WRAP_ALL);
+        assert_eq!((lone.head().point, lone.head().default_direction), ((2, 1), 'L'));
+        assert!(pending(&lone, 0).is_empty());
+    }
+
+    #[test]
+    fn segments_behind_take_the_same_checkpoints_later() {
+        // one list for the whole snake, each segment only forgets a checkpoint once the tail has taken it
+        let mut snake = Snake::with_length(3).unwrap();
+        snake.add_checkpoint(1, 2, 'D').unwrap();
+        snake.update(WRAP_ALL);
+        assert_eq!([pending(&snake, 0).len(), pending(&snake, 1).len(), pending(&snake, 2).len()], [0, 1, 1]);
+        snake.update(WRAP_ALL);
+        assert_eq!([pending(&snake, 0).len(), pending(&snake, 1).len(), pending(&snake, 2).len()], [0, 0, 1]);
+        snake.update(WRAP_ALL);
+        assert!(snake.turns.is_empty());
+        let points: std::vec::Vec<(i8, i8)> = snake.segments.iter().map(|segment| segment.point).collect();
+        assert_eq!(points, [(4, 2), (3, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn repeated_turns_are_only_queued_once() {
+        let mut lone = snake_of(&[segment((1, 1), (1, 0), 'R')]);
+        for _ in 0..CHECKPOINT_CAPACITY * 2 {
+            lone.add_checkpoint(1, 3, 'D').unwrap();
+        }
+        assert_eq!(lone.turns.len(), 1);
+
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for _ in 0..CHECKPOINT_CAPACITY * 2 {
+            jungle.update(Some('D'));
+        }
+        assert!(jungle.snake.turns.len() <= 1);
+    }
+
+    #[test]
+    fn full_checkpoint_queue_rejects_the_newest_turn() {
+        let mut lone = snake_of(&[segment((0, 0), (0, 4), 'R')]);
+        for i in 0..CHECKPOINT_CAPACITY {
+            lone.add_checkpoint(0, (i % BOARD_SIZE) as i8, if i % 2 == 0 { 'D' } else { 'U' }).unwrap();
+        }
+        assert_eq!(lone.add_checkpoint(4, 4, 'L'), Err(CheckpointError::Full));
+        assert_eq!(lone.turns.len(), CHECKPOINT_CAPACITY);
+        assert_eq!(lone.turns[0], (0, 0, 'D'));
+
+        // in a game the turn isn't lost, it waits in the queue until there's room for it
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for i in 0..CHECKPOINT_CAPACITY {
+            jungle.snake.add_checkpoint(4, 4, if i % 2 == 0 { 'D' } else { 'U' }).unwrap();
+        }
+        jungle.update(Some('D'));
+        assert_eq!(jungle.heading(), 'R');
+        assert!(jungle.has_queued_turns());
+        jungle.snake.turns.clear();
+        jungle.update(None);
+        assert_eq!(jungle.heading(), 'D');
+    }
+
+    #[test]
+    fn checkpoints_off_the_board_or_without_a_direction_are_refused() {
+        let mut lone = snake_of(&[segment((0, 0), (0, 4), 'R')]);
+        assert_eq!(lone.add_checkpoint(0, 1, 'X'), Err(CheckpointError::BadDirection('X')));
+        assert_eq!(lone.add_checkpoint(-1, 2, 'D'), Err(CheckpointError::OffBoard(-1, 2)));
+        assert_eq!(lone.add_checkpoint(0, BOARD_SIZE as i8, 'D'), Err(CheckpointError::OffBoard(0, BOARD_SIZE as i8)));
+        assert!(lone.turns.is_empty());
+        assert_eq!(lone.add_checkpoint(0, 1, 'D'), Ok(()));
+    }
+
+    #[test]
+    fn a_full_length_snake_turning_every_tick_stays_whole() {
+        /*
+        Grows the snake to every segment it can have while it zigzags, turning on every tick,
+        so the tail always has as many checkpoints ahead of it as the snake can lay.
+        The body is harmless so the zigzag can cross over itself. Every turn that's queued
+        has to be taken, the length has to hold, and the body has to stay in one piece.
+        */
+        let mut jungle = Jungle::new(Snake::new(), (2, 0), LcgRng::new(1));
+        jungle.deadly_body = false;
+        jungle.growth_pending = (SNAKE_CAPACITY - 2) as u8;
+        let mut queued = 0;
+        let mut taken = 0;
+        let mut most_pending = 0;
+        for tick in 0..200 {
+            // the zigzag never goes through (2, 0), so the nugget stays put
+            jungle.nugget = (2, 0);
+            jungle.nugget_age = 0;
+            if tick < 150 && !jungle.has_queued_turns() {
+                let turn = if jungle.intended_heading() == 'R' { 'D' } else { 'R' };
+                assert!(jungle.queue_direction(turn), "turn refused on tick {}", tick);
+                queued += 1;
+            }
+            let heading = jungle.heading();
+            assert_eq!(jungle.update(None), None, "tick {}", tick);
+            if jungle.heading() != heading {
+                taken += 1;
+            }
+            jungle.assert_contiguous();
+            most_pending = most_pending.max(pending(&jungle.snake, jungle.length() - 1).len());
+        }
+        assert_eq!(jungle.length(), SNAKE_CAPACITY);
+        assert_eq!(taken, queued);
+        assert!(!jungle.has_queued_turns());
+        assert_eq!(most_pending, SNAKE_CAPACITY - 1);
+    }
+
+    #[test]
+    fn snake_can_start_longer() {
+        let snake = Snake::with_length(4).unwrap();
+        let points: std::vec::Vec<(i8, i8)> = snake.segments.iter().map(|segment| segment.point).collect();
+        assert_eq!(points, [(1, 3), (1, 2), (1, 1), (1, 0)]);
+        assert!(snake.segments.iter().all(|segment| segment.default_direction == 'R'));
+        assert_eq!(snake.segments[3].prev_point, (1, BOARD_SIZE as i8 - 1));
+
+        assert!(Snake::with_length(0).is_none());
+        assert!(Snake::with_length(BOARD_SIZE + 1).is_none());
+    }
+
+    #[test]
+    fn snake_can_start_anywhere() {
+        let snake = Snake::new_at((3, 2), 'U', 2).unwrap();
+        let points: std::vec::Vec<(i8, i8)> = snake.segments.iter().map(|segment| segment.point).collect();
+        assert_eq!(points, [(3, 2), (4, 2)]);
+        assert!(snake.segments.iter().all(|segment| segment.default_direction == 'U'));
+        // the cell behind the tail is across the edge, where it would have come from
+        assert_eq!(snake.segments[1].prev_point, (0, 2));
+
+        let snake = Snake::new_at((4, 0), 'L', 5).unwrap();
+        assert_eq!(snake.head().point, (4, 0));
+        assert_eq!(snake.segments[4].point, (4, 4));
+
+        // the body can't run off the board, and the heading has to be one of the four
+        assert!(Snake::new_at((3, 2), 'U', 3).is_none());
+        assert!(Snake::new_at((1, 1), 'R', 3).is_none());
+        assert!(Snake::new_at((1, 1), 'X', 1).is_none());
+        assert!(Snake::new_at((5, 1), 'R', 1).is_none());
+        assert!(Snake::new_at((1, 1), 'R', 0).is_none());
+    }
+
+    #[test]
+    fn snake_occupies_its_cells_across_the_edge() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for _ in 0..4 {
+            jungle.update(None);
+        }
+        assert_eq!(jungle.snake.head().point, (1, 0));
+        assert!(jungle.snake.occupies(1, 0));
+        assert!(jungle.snake.occupies(1, 4));
+        assert!(!jungle.snake.occupies(1, 1));
+        assert!(!jungle.snake.occupies(0, 0));
+    }
+
+    #[test]
+    fn body_turns_where_the_head_turned() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.update(Some('D'));
+        assert_eq!(points(&jungle), [(2, 1), (1, 1)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(3, 1), (2, 1)]);
+        assert!(jungle.snake.turns.is_empty());
+    }
+
+    #[test]
+    fn queued_turns_are_taken_one_per_tick() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert!(jungle.queue_direction('D'));
+        assert!(!jungle.queue_direction('D'));
+        assert!(!jungle.queue_direction('U'));
+        assert!(jungle.queue_direction('L'));
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(2, 1), (1, 1)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(2, 0), (2, 1)]);
+        assert!(!jungle.has_queued_turns());
+    }
+
+    #[test]
+    fn reversing_is_ignored() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.update(Some('L'));
+        assert_eq!(points(&jungle), [(1, 2), (1, 1)]);
+        assert_eq!(jungle.previous_direction, 'R');
+        assert!(!jungle.game_over);
+    }
+
+    #[test]
+    fn opposite_pairs() {
+        assert!(is_opposite('R', 'L'));
+        assert!(is_opposite('L', 'R'));
+        assert!(is_opposite('U', 'D'));
+        assert!(is_opposite('D', 'U'));
+        assert!(!is_opposite('R', 'U'));
+        assert!(!is_opposite('R', 'R'));
+    }
+
+    #[test]
+    fn eating_grows_into_the_cell_the_tail_left() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 2), LcgRng::new(1));
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 2), (1, 1), (1, 0)]);
+        assert_eq!(jungle.score, 1);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 3), (1, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn score_and_ticks_stop_at_the_top() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 2), LcgRng::new(1));
+        jungle.score = u32::MAX;
+        jungle.ticks = u32::MAX;
+        jungle.update(None);
+        assert_eq!(jungle.length(), 3);
+        assert_eq!(jungle.score, u32::MAX);
+        assert_eq!(jungle.ticks, u32::MAX);
+    }
+
+    #[test]
+    fn previous_points_follow_every_move() {
+        let prev_points = |jungle: &Jungle| -> std::vec::Vec<(i8, i8)> {
+            jungle.snake.segments.iter().map(|segment| segment.prev_point).collect()
+        };
+        // straight on, every segment was last where it's just moved from
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert_eq!(prev_points(&jungle), [(1, 0), (1, 4)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 2), (1, 1)]);
+        assert_eq!(prev_points(&jungle), [(1, 1), (1, 0)]);
+
+        // round a corner the head comes from above the cell it's on, and the tail still from its left
+        jungle.update(Some('D'));
+        assert_eq!(points(&jungle), [(2, 2), (1, 2)]);
+        assert_eq!(prev_points(&jungle), [(1, 2), (1, 1)]);
+        jungle.update(None);
+        assert_eq!(prev_points(&jungle), [(2, 2), (1, 2)]);
+
+        // across the bottom edge the previous point is on the other side of the board
+        jungle.update(None);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(0, 2), (4, 2)]);
+        assert_eq!(prev_points(&jungle), [(4, 2), (3, 2)]);
+
+        // a head held back on a slow cell hasn't moved, so it keeps the previous point it had
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.add_slow_cell(1, 2);
+        jungle.update(None);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 2), (1, 1)]);
+        assert_eq!(prev_points(&jungle), [(1, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn growing_on_the_way_out_of_a_turn_follows_it() {
+        // the new tail starts behind the corner, and still has to take the turn the rest took
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 4), LcgRng::new(1));
+        jungle.update(Some('D'));
+        jungle.nugget = (3, 2);
+        assert_eq!(jungle.update(None), Some(GameEvent::Ate));
+        assert_eq!(points(&jungle), [(3, 2), (2, 2), (1, 2), (1, 1)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(4, 2), (3, 2), (2, 2), (1, 2)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(0, 2), (4, 2), (3, 2), (2, 2)]);
+        assert_eq!(checkpoint_count(&jungle), 0);
+    }
+
+    #[test]
+    fn board_is_redrawn_after_eating() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 2), LcgRng::new(1));
+        jungle.update(None);
+
+        let mut expected = [[0; BOARD_SIZE]; BOARD_SIZE];
+        expected[1][2] = HEAD_LEVEL;
+        expected[1][1] = BODY_LEVEL;
+        expected[1][0] = TAIL_LEVEL;
+        expected[1][3] = HEADING_HINT_LEVEL;
+        expected[jungle.nugget.0 as usize][jungle.nugget.1 as usize] = NUGGET_LEVEL;
+        assert_ne!(jungle.nugget, (1, 2));
+        assert_eq!(jungle.basemap, expected);
+    }
+
+    #[test]
+    fn update_reports_what_happened() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 3), LcgRng::new(1));
+        assert_eq!(jungle.update(None), None);
+        assert_eq!(jungle.update(None), Some(GameEvent::Ate));
+
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        for _ in 0..3 {
+            jungle.update(None);
+        }
+        assert_eq!(jungle.update(None), Some(GameEvent::Died(DeathCause::Wall)));
+        assert_eq!(jungle.update(None), None);
+    }
+
+    #[test]
+    fn growing_downwards_across_the_top_edge() {
+        let mut jungle = jungle_with(&[segment((1, 2), (0, 2), 'D'), segment((0, 2), (4, 2), 'D')], (2, 2));
+        jungle.previous_direction = 'D';
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(2, 2), (1, 2), (0, 2)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(3, 2), (2, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn head_can_take_the_cell_the_tail_leaves() {
+        // a snake chasing its tail round a 2x2 loop, the head enters the tail's cell on the tick the tail leaves it
+        let ring = [
+            segment((1, 0), (1, 1), 'U'),
+            segment((1, 1), (0, 1), 'L'),
+            segment((0, 1), (0, 0), 'D'),
+            segment((0, 0), (1, 0), 'R'),
+        ];
+        let mut jungle = jungle_with(&ring, (4, 4));
+        jungle.previous_direction = 'U';
+        jungle.update(None);
+        assert!(!jungle.game_over);
+        assert_eq!(points(&jungle), [(0, 0), (1, 0), (1, 1), (0, 1)]);
+    }
+
+    #[test]
+    fn the_new_tail_is_spared_for_a_tick_after_growing() {
+        // a snake going round a 2x2 loop eats as it enters the cell its tail is leaving, and the
+        // new tail appended there moves into the next cell on the loop just as the head does
+        let mut jungle = Jungle::new(Snake::with_length(4).unwrap(), (4, 4), LcgRng::new(1));
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        jungle.nugget = (1, 2);
+        jungle.update(Some('U'));
+        assert_eq!(jungle.score, 1);
+        assert_eq!(points(&jungle), [(1, 2), (2, 2), (2, 3), (1, 3), (1, 2)]);
+
+        jungle.update(Some('R'));
+        assert!(!jungle.game_over);
+        assert_eq!(points(&jungle), [(1, 3), (1, 2), (2, 2), (2, 3), (1, 3)]);
+
+        // the grace is over, coming round the loop again into the tail ends the game
+        jungle.update(Some('D'));
+        assert!(jungle.game_over);
+    }
+
+    #[test]
+    fn dying_leaves_the_snake_where_it_was() {
+        // the head moves up into the cell the last segment moves into
+        let body = [
+            segment((2, 2), (3, 2), 'U'),
+            segment((2, 3), (2, 4), 'L'),
+            segment((1, 3), (0, 3), 'D'),
+            segment((1, 2), (1, 1), 'R'),
+            segment((1, 1), (1, 0), 'R'),
+        ];
+        let mut jungle = jungle_with(&body, (4, 4));
+        jungle.previous_direction = 'U';
+        let before = points(&jungle);
+        jungle.update(None);
+        assert!(jungle.game_over);
+        assert_eq!(points(&jungle), before);
+    }
+
+    #[test]
+    fn wrapping_onto_the_body_at_every_edge() {
+        // the head wraps across each edge into a corner, where a body segment that wrapped
+        // across the neighbouring edge also lands. The middle segment just joins the two up
+        let cases = [
+            ((0, 4), 'R', (4, 4), 'D', (4, 0), 'D'),
+            ((0, 0), 'L', (4, 0), 'D', (4, 4), 'D'),
+            ((0, 4), 'U', (0, 0), 'R', (4, 0), 'L'),
+            ((4, 0), 'D', (4, 4), 'L', (0, 4), 'R'),
+        ];
+        for &(head, heading, middle, middle_heading, body, body_heading) in cases.iter() {
+            let snake = [segment(head, head, heading), segment(middle, middle, middle_heading), segment(body, body, body_heading)];
+            let mut jungle = jungle_with(&snake, (2, 2));
+            jungle.previous_direction = heading;
+            jungle.update(None);
+            assert!(jungle.game_over, "head {:?} going {} should hit {:?}", head, heading, body);
+        }
+    }
+
+    #[test]
+    fn wrapping_into_a_cell_being_left_is_safe() {
+        // the same wraps, but the segment on the landing cell moves off it on the same tick
+        let cases = [
+            ((1, 4), 'R', (1, 0), 'D'),
+            ((1, 0), 'L', (1, 4), 'D'),
+            ((0, 1), 'U', (4, 1), 'R'),
+            ((4, 1), 'D', (0, 1), 'R'),
+        ];
+        for &(head, heading, body, body_heading) in cases.iter() {
+            let mut jungle = jungle_with(&[segment(head, head, heading), segment(body, body, body_heading)], (2, 2));
+            jungle.previous_direction = heading;
+            jungle.update(None);
+            assert!(!jungle.game_over, "head {:?} going {} shouldn't hit {:?}", head, heading, body);
+            assert_eq!(jungle.head(), body);
+        }
+    }
+
+    #[test]
+    fn going_straight_leaves_no_checkpoints() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 4), LcgRng::new(1));
+        for _ in 0..3 {
+            jungle.update(Some('R'));
+            assert_eq!(checkpoint_count(&jungle), 0);
+            assert_eq!(jungle.heading(), 'R');
+        }
+    }
+
+    #[test]
+    fn a_turn_is_committed_once() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 4), LcgRng::new(1));
+        jungle.update(Some('D'));
+        assert_eq!(jungle.heading(), 'D');
+        // the head took its checkpoint straight away, the two behind it still have theirs
+        assert_eq!(checkpoint_count(&jungle), 2);
+
+        // the same direction again isn't a new turn
+        jungle.update(Some('D'));
+        assert_eq!(jungle.heading(), 'D');
+        assert_eq!(checkpoint_count(&jungle), 1);
+        jungle.update(Some('D'));
+        assert_eq!(checkpoint_count(&jungle), 0);
+        assert_eq!(points(&jungle), [(4, 2), (3, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn two_quick_turns_take_a_cell_each() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 4), LcgRng::new(1));
+        jungle.queue_direction('D');
+        jungle.queue_direction('L');
+        jungle.update(None);
+        assert_eq!(jungle.heading(), 'D');
+        assert_eq!(points(&jungle), [(2, 2), (1, 2), (1, 1)]);
+        jungle.update(None);
+        assert_eq!(jungle.heading(), 'L');
+        assert_eq!(points(&jungle), [(2, 1), (2, 2), (1, 2)]);
+        jungle.update(None);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(2, 4), (2, 0), (2, 1)]);
+        assert_eq!(checkpoint_count(&jungle), 0);
+    }
+
+    #[test]
+    fn a_second_turn_on_a_slow_cell_waits_for_the_next_cell() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 4), LcgRng::new(1));
+        jungle.add_slow_cell(1, 3);
+        jungle.update(None);
+        // held back on the slow cell, the head turns but stays put
+        jungle.update(Some('D'));
+        assert_eq!(points(&jungle), [(1, 3), (1, 2), (1, 1)]);
+        // the second turn comes in on the same cell, so it waits
+        jungle.update(Some('L'));
+        assert_eq!(jungle.heading(), 'D');
+        assert_eq!(points(&jungle), [(2, 3), (1, 3), (1, 2)]);
+        jungle.update(None);
+        assert_eq!(jungle.heading(), 'L');
+        assert_eq!(points(&jungle), [(2, 2), (2, 3), (1, 3)]);
+        jungle.update(None);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(2, 0), (2, 1), (2, 2)]);
+        assert_eq!(checkpoint_count(&jungle), 0);
+    }
+
+    #[test]
+    fn a_harmless_body_is_crossed_over() {
+        let line = [
+            segment((2, 4), (2, 3), 'R'),
+            segment((2, 3), (2, 2), 'R'),
+            segment((2, 2), (2, 1), 'R'),
+            segment((2, 1), (2, 0), 'R'),
+            segment((2, 0), (2, 4), 'R'),
+        ];
+        let mut jungle = jungle_with(&line, (0, 0));
+        jungle.deadly_body = false;
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        jungle.update(Some('U'));
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.head(), (2, 3));
+        jungle.update(None);
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.head(), (1, 3));
+    }
+
+    #[test]
+    fn a_fixed_pace_ignores_the_length() {
+        let mut jungle = Jungle::new(Snake::with_length(2).unwrap(), (4, 4), LcgRng::new(1));
+        jungle.fixed_tick_ms = Some(900);
+        assert_eq!(jungle.tick_ms(), 900);
+        let mut longer = Jungle::new(Snake::with_length(5).unwrap(), (4, 4), LcgRng::new(1));
+        longer.fixed_tick_ms = Some(900);
+        assert_eq!(longer.tick_ms(), 900);
+        longer.set_speed_setting(1);
+        assert_eq!(longer.tick_ms(), 1500);
+    }
+
+    #[test]
+    #[should_panic(expected = "body broken")]
+    fn a_broken_body_is_caught() {
+        let jungle = jungle_with(&[segment((2, 2), (2, 1), 'R'), segment((2, 0), (2, 4), 'R')], (0, 0));
+        jungle.assert_contiguous();
+    }
+
+    #[test]
+    fn a_time_attack_ends_when_the_clock_runs_out() {
+        let mut jungle = Jungle::new(Snake::with_length(2).unwrap(), (1, 2), LcgRng::new(1));
+        jungle.start_time_attack(4);
+        assert_eq!(jungle.update(None), Some(GameEvent::Ate));
+        assert_eq!(jungle.ticks_remaining(), Some(3));
+        jungle.update(None);
+        jungle.update(None);
+        assert_eq!(jungle.update(None), Some(GameEvent::TimeUp));
+        assert!(jungle.game_over);
+        assert_eq!(jungle.death_cause(), Some(DeathCause::Timeout));
+        assert_eq!(jungle.score, 1);
+        assert_eq!(jungle.ticks_remaining(), Some(0));
+        assert_eq!(jungle.update(None), None);
+    }
+
+    #[test]
+    fn the_clock_goes_round_the_edge() {
+        let mut cells = std::vec::Vec::new();
+        for index in 0..EDGE_CELLS {
+            let cell = edge_cell(index);
+            assert!(cell.0 == 0 || cell.1 == 0 || cell.0 == 4 || cell.1 == 4);
+            assert!(!cells.contains(&cell));
+            cells.push(cell);
+        }
+        assert_eq!(cells[..6], [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (1, 4)]);
+
+        // the first so many cells round the edge are lit, by the clock or whatever is on top of it
+        let check_clock = |jungle: &Jungle, lit: usize| {
+            for index in 0..EDGE_CELLS {
+                let cell = edge_cell(index);
+                let level = jungle.basemap[cell.0 as usize][cell.1 as usize];
+                if index < lit {
+                    assert_ne!(level, 0, "{:?} should be lit", cell);
+                } else {
+                    assert_ne!(level, CLOCK_LEVEL, "{:?} should be out", cell);
+                }
+            }
+        };
+        let mut jungle = Jungle::new(Snake::with_length(2).unwrap(), (2, 2), LcgRng::new(1));
+        jungle.start_time_attack(8);
+        check_clock(&jungle, EDGE_CELLS);
+        for _ in 0..4 {
+            jungle.update(Some('D'));
+        }
+        assert_eq!(jungle.ticks_remaining(), Some(4));
+        check_clock(&jungle, EDGE_CELLS / 2);
+    }
+
+    #[test]
+    fn running_into_the_body_ends_the_game() {
+        let line = [
+            segment((2, 4), (2, 3), 'R'),
+            segment((2, 3), (2, 2), 'R'),
+            segment((2, 2), (2, 1), 'R'),
+            segment((2, 1), (2, 0), 'R'),
+            segment((2, 0), (2, 4), 'R'),
+        ];
+        let mut jungle = jungle_with(&line, (0, 0));
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.update(Some('U')), Some(GameEvent::Died(DeathCause::SelfCollision)));
+        assert!(jungle.game_over);
+
+        let frozen = points(&jungle);
+        jungle.update(Some('R'));
+        assert_eq!(points(&jungle), frozen);
+    }
+
+    #[test]
+    fn metronome_climbs_with_the_speed_and_stays_short() {
+        assert_eq!(metronome_hz(tick_delay_ms(2, false)), METRONOME_BASE_HZ);
+        assert_eq!(metronome_hz(tick_delay_ms(4, false)), METRONOME_BASE_HZ + 2 * METRONOME_STEP_HZ);
+        assert_eq!(metronome_hz(1), METRONOME_MAX_HZ);
+
+        // never lower for a shorter tick, and never more than its share of the tick
+        for tick_ms in 1..=2 * BASE_TICK_MS {
+            assert!(metronome_hz(tick_ms) >= metronome_hz(tick_ms + 1), "{}ms", tick_ms);
+            assert!(metronome_click_ms(tick_ms) >= 1);
+            assert!(metronome_click_ms(tick_ms) <= METRONOME_CLICK_MS);
+            if tick_ms >= METRONOME_TICK_SHARE {
+                assert!(metronome_click_ms(tick_ms) * METRONOME_TICK_SHARE <= tick_ms, "{}ms", tick_ms);
+            }
+        }
+    }
+
+    #[test]
+    fn game_speeds_up_as_the_snake_grows() {
+        assert_eq!(tick_delay_ms(2, false), 500);
+        assert_eq!(tick_delay_ms(3, false), 475);
+        assert!(tick_delay_ms(10, false) < tick_delay_ms(9, false));
+        assert_eq!(tick_delay_ms(SNAKE_CAPACITY, false), 120);
+        assert_eq!(speed_level(tick_delay_ms(2, false)), 0);
+        assert_eq!(speed_level(tick_delay_ms(4, false)), 2);
+        assert_eq!(tick_delay_ms(2, true), 250);
+    }
+
+    #[test]
+    fn speed_nugget_boosts_without_growing() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.speed_nugget = Some((1, 2));
+        jungle.update(None);
+        assert_eq!(jungle.length(), 2);
+        assert_eq!(jungle.score, 0);
+        assert!(jungle.speed_nugget.is_none());
+        assert!(jungle.boosted());
+        for _ in 0..BOOST_TICKS {
+            jungle.update(None);
+        }
+        assert!(!jungle.boosted());
+    }
+
+    #[test]
+    fn filling_the_board_wins() {
+        // a snake winding through every cell but the nugget, each segment heading for the one ahead
+        let mut path = std::vec::Vec::new();
+        for y in (0..4).rev() {
+            path.push((0, y));
+        }
+        for x in 1..5 {
+            for y in 0..5 {
+                path.push((x, if x % 2 == 1 { y } else { 4 - y }));
+            }
+        }
+        let segments: std::vec::Vec<Segment> = path.iter().enumerate().map(|(i, &point)| {
+            let ahead: (i8, i8) = if i == 0 { (0, 4) } else { path[i - 1] };
+            let direction = if ahead.1 > point.1 { 'R' } else if ahead.1 < point.1 { 'L' } else if ahead.0 > point.0 { 'D' } else { 'U' };
+            segment(point, point, direction)
+        }).collect();
+
+        let mut jungle = jungle_with(&segments, (0, 4));
+        jungle.update(None);
+        assert!(jungle.won);
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.length(), SNAKE_CAPACITY);
+    }
+
+    #[test]
+    fn commands_set_the_mode_flags() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert!(apply_command(&mut jungle, "W0"));
+        assert_eq!(jungle.wrap, WRAP_NONE);
+        assert!(apply_command(&mut jungle, "w1\r"));
+        assert_eq!(jungle.wrap, WRAP_ALL);
+
+        assert!(apply_command(&mut jungle, "D0"));
+        assert!(!jungle.deadly_body);
+        assert!(apply_command(&mut jungle, "D1"));
+        assert!(jungle.deadly_body);
+
+        assert!(apply_command(&mut jungle, "S8"));
+        assert_eq!(jungle.speed_setting(), 8);
+
+        assert!(apply_command(&mut jungle, "G3"));
+        assert_eq!(jungle.growth_per_nugget, 3);
+    }
+
+    #[test]
+    fn bad_commands_change_nothing() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for &command in ["", "W", "W2", "D-1", "S0", "S10", "G0", "G6", "X1", "W1 1"].iter() {
+            assert!(!apply_command(&mut jungle, command), "{:?}", command);
+        }
+        assert_eq!(jungle.wrap, WRAP_ALL);
+        assert!(jungle.deadly_body);
+        assert_eq!(jungle.speed_setting(), NEUTRAL_SPEED_SETTING);
+        assert_eq!(jungle.growth_per_nugget, 1);
+    }
+
+    #[test]
+    fn a_bigger_growth_adds_a_segment_a_tick() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 2), LcgRng::new(1));
+        jungle.growth_per_nugget = 3;
+        jungle.update(None);
+        assert_eq!(jungle.score, 1);
+        assert_eq!(points(&jungle), [(1, 2), (1, 1), (1, 0)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 3), (1, 2), (1, 1), (1, 0)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 4), (1, 3), (1, 2), (1, 1), (1, 0)]);
+        jungle.update(None);
+        assert_eq!(jungle.length(), 5);
+        assert_eq!(jungle.score, 1);
+    }
+
+    #[test]
+    fn speed_setting_scales_the_tick() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert_eq!(jungle.tick_ms(), tick_delay_ms(2, false));
+        assert_eq!(jungle.set_speed_setting(9), 9);
+        assert!(jungle.tick_ms() < tick_delay_ms(2, false));
+        assert_eq!(jungle.set_speed_setting(1), 1);
+        assert!(jungle.tick_ms() > tick_delay_ms(2, false));
+        assert_eq!(jungle.set_speed_setting(0), 1);
+        assert_eq!(jungle.set_speed_setting(42), MAX_SPEED_SETTING);
+    }
+
+    #[test]
+    fn full_snake_refuses_another_segment() {
+        let mut snake = snake_of(&[]);
+        for i in 0..SNAKE_CAPACITY {
+            let point = ((i / BOARD_SIZE) as i8, (i % BOARD_SIZE) as i8);
+            assert!(snake.add_segment(segment(point, point, 'R')).is_ok());
+        }
+        assert_eq!(snake.add_segment(segment((0, 0), (0, 0), 'R')), Err(SnakeFull));
+        assert_eq!(snake.segments.len(), SNAKE_CAPACITY);
+    }
+
+    #[test]
+    fn autopilot_heads_for_the_nugget() {
+        let jungle = Jungle::new(Snake::new(), (3, 1), LcgRng::new(1));
+        assert_eq!(autopilot_direction(&jungle), 'D');
+        let jungle = Jungle::new(Snake::new(), (1, 3), LcgRng::new(1));
+        assert_eq!(autopilot_direction(&jungle), 'R');
+    }
+
+    #[test]
+    fn autopilot_never_reverses_or_hits_a_wall() {
+        // nugget behind the head against the top wall, so it has to go round
+        let mut jungle = jungle_with(&[segment((0, 2), (0, 1), 'R'), segment((0, 1), (0, 0), 'R')], (1, 0));
+        jungle.wrap = WRAP_NONE;
+        assert_eq!(autopilot_direction(&jungle), 'D');
+    }
+
+    #[test]
+    fn uneaten_nugget_moves_after_a_while() {
+        // the snake runs along row 1 and never reaches the nugget in row 4
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for _ in 0..NUGGET_TIMEOUT_TICKS {
+            jungle.update(None);
+        }
+        assert_eq!(jungle.nugget, (4, 4));
+        assert_eq!(jungle.nugget_age, NUGGET_TIMEOUT_TICKS);
+
+        jungle.update(None);
+        assert_eq!(jungle.nugget_age, 0);
+        assert!(jungle.nugget_fits(jungle.nugget.0 as i8, jungle.nugget.1 as i8));
+    }
+
+    #[test]
+    fn second_snake_starts_clear_and_dimmer() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert!(jungle.add_rival(Snake::rival_with_length(2).unwrap(), 'L'));
+        assert_eq!(jungle.basemap[3][3], RIVAL_HEAD_LEVEL);
+        assert_eq!(jungle.basemap[3][4], RIVAL_BODY_LEVEL);
+        assert_eq!(jungle.basemap[1][1], HEAD_LEVEL);
+        assert!(!jungle.add_rival(Snake::new(), 'L'));
+    }
+
+    #[test]
+    fn snakes_meeting_head_on_end_the_game() {
+        // heads a cell apart meet in the middle, heads side by side swap cells
+        for &rival_head in [(1, 3), (1, 2)].iter() {
+            let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+            assert!(jungle.add_rival(snake_of(&[segment(rival_head, rival_head, 'L')]), 'L'));
+            assert_eq!(jungle.update_both(None, None), Some(GameEvent::Died(DeathCause::Rival)));
+            assert!(jungle.game_over);
+        }
+    }
+
+    #[test]
+    fn second_snake_eats_and_scores_on_its_own() {
+        let mut jungle = Jungle::new(Snake::new(), (3, 2), LcgRng::new(1));
+        assert!(jungle.add_rival(Snake::rival_with_length(2).unwrap(), 'L'));
+        assert_eq!(jungle.update_both(None, None), Some(GameEvent::Ate));
+        assert_eq!((jungle.score, jungle.rival_score), (0, 1));
+        assert_eq!(jungle.rival.as_ref().unwrap().segments.len(), 3);
+
+        // and steers with its own direction
+        jungle.update_both(None, Some('U'));
+        assert_eq!(jungle.rival.as_ref().unwrap().head().point, (2, 2));
+        assert!(!jungle.game_over);
+    }
+
+    #[test]
+    fn game_time_follows_the_ticks() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for _ in 0..4 {
+            jungle.update(None);
+        }
+        jungle.paused = true;
+        jungle.update(None);
+        assert_eq!(jungle.ticks(), 4);
+        assert_eq!(jungle.elapsed_secs(), 4 * tick_delay_ms(2, false) / 1000);
+    }
+
+    #[test]
+    fn board_dumps_as_ascii() {
+        let mut jungle = Jungle::new(Snake::new(), (3, 2), LcgRng::new(1));
+        jungle.obstacles.push((4, 0)).unwrap();
+        let mut text = String::new();
+        render_ascii(&jungle, &mut text).unwrap();
+        assert_eq!(text, ".....\r\n#O...\r\n.....\r\n..*..\r\nX....\r\n");
+    }
+
+    #[test]
+    fn running_into_an_obstacle_ends_the_game() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.obstacles.push((1, 2)).unwrap();
+        assert_eq!(jungle.update(None), Some(GameEvent::Died(DeathCause::Hazard)));
+        assert!(jungle.game_over);
+    }
+
+    #[test]
+    fn a_body_crossing_a_turn_waits_its_turn_there() {
+        // a snake that crosses over itself, round and round the top row with both ends on (0, 1).
+        // The head turns there, and the tail already on that cell carries on along the row, it
+        // only takes the turn once it comes back round behind the rest of the body
+        let row = [(0, 1), (0, 0), (0, 4), (0, 3), (0, 2), (0, 1), (0, 0)];
+        let segments: std::vec::Vec<Segment> = row.iter().map(|&point| segment(point, (point.0, (point.1 + 4) % 5), 'R')).collect();
+        let mut jungle = jungle_with(&segments, (4, 4));
+        jungle.deadly_body = false;
+        jungle.update(Some('D'));
+        assert_eq!(points(&jungle), [(1, 1), (0, 1), (0, 0), (0, 4), (0, 3), (0, 2), (0, 1)]);
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(2, 1), (1, 1), (0, 1), (0, 0), (0, 4), (0, 3), (0, 2)]);
+        for _ in 0..5 {
+            jungle.update(None);
+        }
+        assert_eq!(points(&jungle), [(2, 1), (1, 1), (0, 1), (4, 1), (3, 1), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn a_reentry_gap_keeps_the_head_off_cells_the_tail_just_left() {
+        let line = [segment((2, 2), (2, 1), 'R'), segment((2, 1), (2, 0), 'R'), segment((2, 0), (2, 4), 'R')];
+
+        // turning straight back round puts the head onto a cell the tail left only a tick ago
+        let mut jungle = jungle_with(&line, (0, 4));
+        jungle.reentry_gap = 2;
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        assert_eq!(jungle.update(Some('U')), Some(GameEvent::Died(DeathCause::SelfCollision)));
+        assert_eq!(jungle.crash_cell(), Some((2, 1)));
+
+        // going a cell wider it was left three ticks before, which is long enough
+        let mut jungle = jungle_with(&line, (0, 4));
+        jungle.reentry_gap = 2;
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        jungle.update(None);
+        assert_eq!(jungle.update(Some('U')), None);
+        assert_eq!(jungle.head(), (2, 0));
+
+        // and without a gap the tight turn is fine
+        let mut jungle = jungle_with(&line, (0, 4));
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        assert_eq!(jungle.update(Some('U')), None);
+        assert_eq!(jungle.head(), (2, 1));
+    }
+
+    #[test]
+    fn a_reentry_gap_stops_the_head_chasing_its_tail() {
+        // round a 2x2 loop the head goes onto the tail's cell on the very tick the tail leaves it, see
+        // head_can_take_the_cell_the_tail_leaves, which leaves it no time at all to stand empty
+        let ring = [
+            segment((1, 0), (1, 1), 'U'),
+            segment((1, 1), (0, 1), 'L'),
+            segment((0, 1), (0, 0), 'D'),
+            segment((0, 0), (1, 0), 'R'),
+        ];
+        let mut jungle = jungle_with(&ring, (4, 4));
+        jungle.previous_direction = 'U';
+        jungle.reentry_gap = 1;
+        assert_eq!(jungle.update(None), Some(GameEvent::Died(DeathCause::SelfCollision)));
+        assert_eq!(points(&jungle), [(1, 0), (1, 1), (0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn going_too_long_without_eating_starves_the_snake() {
+        // a nugget eaten on the second tick starts the count again
+        let mut jungle = Jungle::new(Snake::new(), (1, 3), LcgRng::new(1));
+        jungle.starvation_ticks = Some(3);
+        jungle.update(None);
+        assert_eq!(jungle.update(None), Some(GameEvent::Ate));
+        jungle.nugget = (4, 0);
+        jungle.update(None);
+        jungle.update(None);
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.update(None), Some(GameEvent::Died(DeathCause::Starvation)));
+        assert_eq!(jungle.death_cause(), Some(DeathCause::Starvation));
+
+        // and by default it never does
+        let mut jungle = Jungle::new(Snake::new(), (4, 0), LcgRng::new(1));
+        for _ in 0..NUGGET_TIMEOUT_TICKS {
+            jungle.update(None);
+        }
+        assert!(!jungle.game_over);
+        assert_eq!(jungle.death_cause(), None);
+    }
+
+    #[test]
+    fn second_player_crashes_record_their_cause() {
+        // player two running into the first snake, an obstacle, or a wall ends the game just the same
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert!(jungle.add_rival(snake_of(&[segment((2, 1), (2, 2), 'L')]), 'L'));
+        assert_eq!(jungle.update_both(None, Some('U')), Some(GameEvent::Died(DeathCause::Rival)));
+
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.obstacles.push((3, 0)).unwrap();
+        assert!(jungle.add_rival(snake_of(&[segment((3, 1), (3, 2), 'L')]), 'L'));
+        assert_eq!(jungle.update_both(None, None), Some(GameEvent::Died(DeathCause::Hazard)));
+
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        assert!(jungle.add_rival(snake_of(&[segment((3, 0), (3, 1), 'L')]), 'L'));
+        assert_eq!(jungle.update_both(None, None), Some(GameEvent::Died(DeathCause::Wall)));
+    }
+
+    #[test]
+    fn an_obstacle_comes_every_few_nuggets_however_much_they_grow() {
+        // a lone head that grows two a nugget, fed a nugget on every tick. It's soon longer than
+        // a row, so it's let cross over itself
+        let mut jungle = Jungle::new(Snake::with_length(1).unwrap(), (1, 1), LcgRng::new(1));
+        jungle.growth_per_nugget = 2;
+        jungle.deadly_body = false;
+        for eaten in 1..=2 * NUGGETS_PER_OBSTACLE {
+            let ahead = step(jungle.head(), jungle.heading(), jungle.wrap).unwrap();
+            jungle.nugget = (ahead.0 as u8, ahead.1 as u8);
+            jungle.obstacles.retain(|&obstacle| obstacle != ahead);
+            let obstacles = jungle.obstacles.len();
+            assert_eq!(jungle.update(None), Some(GameEvent::Ate));
+            let expected = if eaten.is_multiple_of(NUGGETS_PER_OBSTACLE) { obstacles + 1 } else { obstacles };
+            assert_eq!(jungle.obstacles.len(), expected, "after nugget {}", eaten);
+        }
+
+        // rerolls take points off the score, but not nuggets off the count
+        jungle.score = 0;
+        jungle.nuggets_eaten = NUGGETS_PER_OBSTACLE - 1;
+        let ahead = step(jungle.head(), jungle.heading(), jungle.wrap).unwrap();
+        jungle.nugget = (ahead.0 as u8, ahead.1 as u8);
+        jungle.obstacles.retain(|&obstacle| obstacle != ahead);
+        let obstacles = jungle.obstacles.len();
+        assert_eq!(jungle.update(None), Some(GameEvent::Ate));
+        assert_eq!(jungle.obstacles.len(), obstacles + 1);
+    }
+
+    #[test]
+    fn a_reroll_moves_the_nugget_far_away_for_a_point() {
+        // with walls all round, the far corner really is the farthest cell from the head on (1, 1)
+        let mut jungle = Jungle::new(Snake::new(), (2, 2), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        jungle.score = 5;
+        assert!(jungle.reroll_nugget());
+        assert_eq!(jungle.nugget, (4, 4));
+        assert_eq!(jungle.score, 5 - REROLL_PENALTY);
+
+        // only so many times for the same nugget, and a refused one costs nothing
+        for _ in 1..MAX_REROLLS_PER_NUGGET {
+            assert!(jungle.reroll_nugget());
+        }
+        assert_eq!(jungle.score, 5 - MAX_REROLLS_PER_NUGGET as u32 * REROLL_PENALTY);
+        assert!(!jungle.reroll_nugget());
+        assert_eq!(jungle.score, 5 - MAX_REROLLS_PER_NUGGET as u32 * REROLL_PENALTY);
+
+        // going round the edges nothing is more than two rows and two columns away
+        let mut jungle = Jungle::new(Snake::new(), (2, 2), LcgRng::new(1));
+        assert!(jungle.reroll_nugget());
+        let nugget = (jungle.nugget.0 as i8, jungle.nugget.1 as i8);
+        assert_eq!(board_distance(jungle.head(), nugget, WRAP_ALL), 4);
+        assert_eq!(jungle.score, 0);
+    }
+
+    #[test]
+    fn obstacles_keep_nuggets_and_themselves_apart() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        for _ in 0..MAX_OBSTACLES {
+            assert!(jungle.place_obstacle());
+        }
+        assert!(!jungle.place_obstacle());
+        for _ in 0..50 {
+            assert!(jungle.place_nugget(NuggetPlacement::Random));
+            let nugget = (jungle.nugget.0 as i8, jungle.nugget.1 as i8);
+            assert!(!jungle.obstacles.contains(&nugget));
+        }
+        for (i, obstacle) in jungle.obstacles.iter().enumerate() {
+            assert!(!jungle.occupied(obstacle.0, obstacle.1));
+            assert!(!jungle.obstacles[i + 1..].contains(obstacle));
+        }
+    }
+
+    #[test]
+    fn save_and_restore_round_trip() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 3), LcgRng::new(7));
+        jungle.update(None);
+        jungle.update(Some('D'));
+        jungle.place_obstacle();
+        let blob = jungle.save_state();
+
+        let mut restored = Jungle::restore(&blob).unwrap();
+        assert_eq!(restored.save_state().as_bytes(), blob.as_bytes());
+
+        for input in [Some('L'), None, Some('U'), None, None] {
+            jungle.update(input);
+            restored.update(input);
+        }
+        assert_eq!(restored.save_state().as_bytes(), jungle.save_state().as_bytes());
+    }
+
+    #[test]
+    fn a_restored_game_plays_on_the_same() {
+        // a game using everything there is to save, part way through
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 0), LcgRng::new(11));
+        assert!(jungle.add_rival(Snake::rival_with_length(3).unwrap(), 'L'));
+        jungle.start_time_attack(60);
+        jungle.wrap = [true, true, false, true];
+        jungle.body_collision = CollisionPolicy::Shrink;
+        jungle.growth_per_nugget = 2;
+        jungle.set_speed_setting(7);
+        jungle.fixed_tick_ms = Some(300);
+        jungle.set_nugget_count(3);
+        jungle.add_slow_cell(0, 3);
+        jungle.invert_display = true;
+        jungle.obstacles.push((2, 4)).unwrap();
+        jungle.update_both(Some('U'), None);
+        jungle.update_both(None, Some('U'));
+        jungle.update_both(Some('R'), Some('R'));
+        jungle.dash();
+        jungle.update_both(None, None);
+        assert!(!jungle.game_over);
+        jungle.queue_direction('R');
+        jungle.queue_direction('D');
+
+        let blob = jungle.save_state();
+        let mut restored = Jungle::restore(&blob).unwrap();
+        assert_eq!(restored.save_state().as_bytes(), blob.as_bytes());
+        assert_eq!(restored.render(), jungle.render());
+
+        // and from there the two can't be told apart, tick by tick
+        let rival_inputs = [Some('U'), None, Some('L'), None, Some('D'), None, None, Some('L'), None, Some('U')];
+        for (tick, &rival_input) in rival_inputs.iter().cycle().take(40).enumerate() {
+            let input = Some(autopilot_direction(&jungle));
+            assert_eq!(restored.update_both(input, rival_input), jungle.update_both(input, rival_input), "tick {}", tick);
+            assert_eq!(restored.render(), jungle.render(), "tick {}", tick);
+            assert_eq!(restored.tick_ms(), jungle.tick_ms(), "tick {}", tick);
+            assert_eq!((restored.score, restored.rival_score), (jungle.score, jungle.rival_score), "tick {}", tick);
+            assert_eq!(restored.ticks_remaining(), jungle.ticks_remaining(), "tick {}", tick);
+        }
+        // it ate a few before it ended, rather than both ending straight away
+        assert!(jungle.score > 0);
+        assert_eq!(restored.save_state().as_bytes(), jungle.save_state().as_bytes());
+    }
+
+    #[test]
+    fn a_resumed_game_keeps_its_second_player_and_clock() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 0), LcgRng::new(5));
+        let blob = jungle.save_state();
+        let restored = Jungle::restore(&blob).unwrap();
+        assert!(!restored.has_rival());
+        assert_eq!(restored.ticks_remaining(), None);
+
+        assert!(jungle.add_rival(Snake::rival_with_length(3).unwrap(), 'L'));
+        jungle.start_time_attack(60);
+        jungle.update_both(None, None);
+        let mut restored = Jungle::restore(&jungle.save_state()).unwrap();
+        assert!(restored.has_rival());
+        assert_eq!(restored.ticks_remaining(), Some(59));
+
+        // and the second player can still steer it
+        restored.update_both(None, Some('U'));
+        assert_eq!(restored.rival_heading, 'U');
+    }
+
+    #[test]
+    fn restore_rejects_a_heading_that_is_not_a_direction() {
+        let jungle = Jungle::new(Snake::new(), (1, 3), LcgRng::new(7));
+        let mut bytes = [0; STATE_BLOB_LEN];
+        bytes.copy_from_slice(jungle.save_state().as_bytes());
+        bytes[3] = b'X';
+        bytes[STATE_BLOB_LEN - 1] = state_checksum(&bytes[..STATE_BLOB_LEN - 1]);
+        assert!(matches!(Jungle::restore(&StateBlob::from_bytes(bytes)), Err(RestoreError::Corrupt)));
+    }
+
+    fn restores_as_corrupt(jungle: &Jungle) -> bool {
+        // saved as it is, so the checksum is good and only the state itself can be wrong
+        matches!(Jungle::restore(&jungle.save_state()), Err(RestoreError::Corrupt))
+    }
+
+    #[test]
+    fn restore_rejects_a_clock_that_cant_run() {
+        let mut jungle = Jungle::new(Snake::new(), (1, 3), LcgRng::new(7));
+        jungle.start_time_attack(20);
+        assert!(Jungle::restore(&jungle.save_state()).is_ok());
+
+        jungle.ticks_remaining = Some(21);
+        assert!(restores_as_corrupt(&jungle));
+        jungle.time_limit = 0;
+        jungle.ticks_remaining = Some(0);
+        assert!(restores_as_corrupt(&jungle));
+    }
+
+    #[test]
+    fn restore_rejects_a_broken_body() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 4), LcgRng::new(7));
+        jungle.snake.segments[2].point = (3, 0);
+        assert!(restores_as_corrupt(&jungle));
+
+        // but a body going round an edge is in one piece
+        let mut jungle = Jungle::new(Snake::new_at((0, 2), 'U', 3).unwrap(), (2, 2), LcgRng::new(7));
+        jungle.wrap = [false; 4];
+        assert!(Jungle::restore(&jungle.save_state()).is_ok());
+        jungle.snake.segments[1].point = (4, 2);
+        jungle.snake.segments[2].point = (3, 2);
+        assert!(Jungle::restore(&jungle.save_state()).is_ok());
+    }
+
+    #[test]
+    fn restore_rejects_snakes_on_each_other_or_an_obstacle() {
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 0), LcgRng::new(7));
+        jungle.obstacles.push((1, 1)).unwrap();
+        assert!(restores_as_corrupt(&jungle));
+
+        let mut jungle = Jungle::new(Snake::with_length(3).unwrap(), (4, 0), LcgRng::new(7));
+        assert!(jungle.add_rival(Snake::rival_with_length(3).unwrap(), 'L'));
+        let rival = jungle.rival.as_mut().unwrap();
+        for (segment, &point) in rival.segments.iter_mut().zip(&[(2, 3), (2, 2), (1, 2)]) {
+            segment.point = point;
+        }
+        assert!(restores_as_corrupt(&jungle));
+
+        // a tail grown onto the cell the other head just moved onto is a board the game can leave
+        jungle.rival.as_mut().unwrap().segments[2].prev_point = (1, 2);
+        assert!(Jungle::restore(&jungle.save_state()).is_ok());
+    }
+
+    #[test]
+    fn snapshot_survives_the_serial_frame() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 3), LcgRng::new(1));
+        jungle.update(Some('D'));
+        jungle.score = 300;
+        jungle.paused = true;
+
+        let state = jungle.snapshot();
+        assert_eq!(state.head, jungle.head());
+        assert_eq!(state.segments(), points(&jungle).as_slice());
+
+        let frame = state.encode();
+        assert_eq!(frame.len(), GAME_STATE_HEADER_LEN + 2);
+        assert_eq!(GameState::decode(&frame), Some(state));
+        assert_eq!(GameState::decode(&frame[..frame.len() - 1]), None);
+    }
+
+    #[test]
+    fn shrinking_bites_off_the_tail_instead_of_dying() {
+        // a snake of five eats its way to six, then turns tightly back into its fifth segment
+        let mut jungle = Jungle::new(Snake::with_length(5).unwrap(), (2, 4), LcgRng::new(1));
+        jungle.body_collision = CollisionPolicy::Shrink;
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        assert_eq!(points(&jungle), [(2, 3), (2, 4), (1, 4), (1, 3), (1, 2), (1, 1)]);
+        jungle.update(Some('U'));
+
+        // the head is on (1, 3), and the segment it landed on and the tail behind it are gone
+        assert!(!jungle.game_over);
+        assert_eq!(points(&jungle), [(1, 3), (2, 3), (2, 4), (1, 4)]);
+        assert_eq!(jungle.score, 1);
+        jungle.assert_contiguous();
+
+        // and it plays on as normal from there
+        jungle.update(None);
+        assert!(!jungle.game_over);
+        assert_eq!(points(&jungle), [(0, 3), (1, 3), (2, 3), (2, 4)]);
+
+        // the same bite ends the game by default
+        let mut jungle = Jungle::new(Snake::with_length(5).unwrap(), (2, 4), LcgRng::new(1));
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        jungle.update(Some('U'));
+        assert!(jungle.game_over);
+    }
+
+    #[test]
+    fn stepping_round_the_board_comes_back_to_the_start() {
+        // with nothing to steer it the snake runs along row 1, wrapping every five steps
+        let mut jungle = Jungle::new(Snake::new(), (3, 3), LcgRng::new(1));
+        for step in 1..=100 {
+            jungle.step(None);
+            assert!(!jungle.game_over);
+            assert_eq!(jungle.head(), (1, (1 + step) % BOARD_SIZE as i8));
+        }
+        assert_eq!(jungle.head(), (1, 1));
+        assert_eq!(jungle.ticks, 100);
+    }
+
+    #[test]
+    fn dashing_moves_two_cells_a_tick_for_a_while() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert!(jungle.dash());
+        jungle.update(None);
+        assert_eq!(points(&jungle), [(1, 3), (1, 2)]);
+        for _ in 1..DASH_TICKS {
+            jungle.update(None);
+        }
+        assert_eq!(jungle.head(), (1, (1 + 2 * DASH_TICKS as i8) % BOARD_SIZE as i8));
+
+        // then it's back to one a tick, and another dash has to wait for the cooldown
+        jungle.update(None);
+        assert_eq!(jungle.head(), (1, (2 + 2 * DASH_TICKS as i8) % BOARD_SIZE as i8));
+        assert!(!jungle.dash());
+        for _ in DASH_TICKS + 1..DASH_COOLDOWN_TICKS {
+            jungle.update(None);
+        }
+        assert!(jungle.dash());
+    }
+
+    #[test]
+    fn dashing_crashes_into_what_is_in_between() {
+        // an obstacle on the cell the first of the two moves lands on isn't jumped over
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.obstacles.push((1, 2)).unwrap();
+        assert!(jungle.dash());
+        assert_eq!(jungle.update(None), Some(GameEvent::Died(DeathCause::Hazard)));
+        assert_eq!(jungle.head(), (1, 1));
+
+        // and a wall one cell past the first move still stops it
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        jungle.update(None);
+        jungle.update(None);
+        assert!(jungle.dash());
+        assert_eq!(jungle.update(None), Some(GameEvent::Died(DeathCause::Wall)));
+        assert_eq!(jungle.head(), (1, 4));
+    }
+
+    #[test]
+    fn queued_turn_blinks_the_head_until_the_next_tick() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert_eq!(jungle.basemap[1][1], HEAD_LEVEL);
+        assert!(jungle.queue_direction('D'));
+        assert_eq!(jungle.basemap[1][1], TURN_FLASH_LEVEL);
+
+        // a turn that isn't taken doesn't blink it again
+        jungle.update(None);
+        assert_eq!(jungle.basemap[2][1], HEAD_LEVEL);
+        assert!(!jungle.queue_direction('D'));
+        assert_eq!(jungle.basemap[2][1], HEAD_LEVEL);
+    }
+
+    #[test]
+    fn extra_nuggets_are_eaten_and_come_back_one_at_a_time() {
+        // every nugget on its own cell, and none of them under the snake
+        let check_spread = |jungle: &Jungle| {
+            let mut cells: std::vec::Vec<(u8, u8)> = jungle.extra_nuggets.to_vec();
+            cells.push(jungle.nugget);
+            for (i, cell) in cells.iter().enumerate() {
+                assert!(!jungle.snake.occupies(cell.0 as i8, cell.1 as i8), "nugget under the snake: {:?}", cell);
+                assert!(!cells[i + 1..].contains(cell), "two nuggets on: {:?}", cell);
+            }
+        };
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        assert_eq!(jungle.set_nugget_count(MAX_NUGGETS + 1), MAX_NUGGETS);
+        check_spread(&jungle);
+
+        // eating an extra one grows the snake and scores, and only that one moves
+        let others: std::vec::Vec<(u8, u8)> = jungle.extra_nuggets[1..].to_vec();
+        assert!(!others.contains(&(1, 2)));
+        jungle.extra_nuggets[0] = (1, 2);
+        jungle.update(None);
+        assert_eq!(jungle.score, 1);
+        assert_eq!(jungle.length(), 3);
+        assert_eq!(jungle.nugget, (4, 4));
+        assert_eq!(jungle.nugget_count(), MAX_NUGGETS);
+        assert!(jungle.extra_nugget_at((1, 2)).is_none());
+        assert!(others.iter().all(|nugget| jungle.extra_nuggets.contains(nugget)));
+        check_spread(&jungle);
+
+        // and going back to one takes the extras off the board
+        assert_eq!(jungle.set_nugget_count(0), 1);
+        assert!(jungle.extra_nuggets.is_empty());
+    }
+
+    #[test]
+    fn running_into_the_body_marks_where() {
+        let mut jungle = Jungle::new(Snake::with_length(5).unwrap(), (2, 4), LcgRng::new(1));
+        jungle.update(Some('D'));
+        jungle.update(Some('L'));
+        assert_eq!(jungle.crash_cell(), None);
+        jungle.update(Some('U'));
+        assert!(jungle.game_over);
+        assert_eq!(jungle.crash_cell(), Some((1, 3)));
+
+        // the rest of the board stays as it ended while the cell flashes
+        let board = jungle.render();
+        for frame in 0..4 {
+            let mut expected = board;
+            expected[1][3] = if frame % 2 == 0 { CRASH_LEVEL } else { 0 };
+            assert_eq!(jungle.crash_frame(frame), expected);
+        }
+
+        // a wall isn't on the board to flash
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        jungle.wrap = WRAP_NONE;
+        for _ in 0..4 {
+            jungle.update(None);
+        }
+        assert!(jungle.game_over);
+        assert_eq!(jungle.crash_cell(), None);
+    }
+
+    #[test]
+    fn modes_read_back_as_commands() {
+        let mut jungle = Jungle::new(Snake::new(), (4, 4), LcgRng::new(1));
+        let mut text = String::new();
+        dump_modes(&jungle, &mut text).unwrap();
+        assert_eq!(text, "W1 D1 S5 G1 N1");
+
+        for command in ["W0", "D0", "S7", "G3", "N4"].iter() {
+            assert!(apply_command(&mut jungle, command));
+        }
+        let mut text = String::new();
+        dump_modes(&jungle, &mut text).unwrap();
+        assert_eq!(text, "W0 D0 S7 G3 N4");
+
+        jungle.wrap = [true, false, false, true];
+        let mut text = String::new();
+        dump_modes(&jungle, &mut text).unwrap();
+        assert_eq!(text, "W1001 D0 S7 G3 N4");
+    }
+
+    #[test]
+    fn score_history_keeps_the_latest_games() {
+        let mut history = ScoreHistory::new();
+        let mut text = String::new();
+        history.dump(&mut text).unwrap();
+        assert_eq!(text, "No games yet\r\n");
+
+        for score in 1..=SCORE_HISTORY_LEN as u32 + 2 {
+            history.push(score);
+        }
+        let mut text = String::new();
+        history.dump(&mut text).unwrap();
+        assert_eq!(text, "1. 7\r\n2. 6\r\n3. 5\r\n4. 4\r\n5. 3\r\n");
+    }
+
+    #[test]
+    fn restore_rejects_an_erased_page() {
+        let blob = StateBlob::from_bytes([0xFF; STATE_BLOB_LEN]);
+        assert!(matches!(Jungle::restore(&blob), Err(RestoreError::BadMagic)));
+    }
+
+    fn roll_free_cell(rng: &mut LcgRng, free: impl Fn(i8, i8) -> bool) -> (u8, u8) {
+        // what place_nugget and place_speed_nugget do: roll a row then a column until the cell is free
+        loop {
+            let x = rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+            let y = rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+            if free(x as i8, y as i8) {
+                return (x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_game_places_nuggets_in_a_fixed_sequence() {
+        /*
+        Plays a seeded game along a fixed route and replays the same draws on a second generator,
+        so a change to how nuggets are placed shows up here rather than as a different game on a seed.
+        The route is four cells right then one down, over and over, which with wrapping edges
+        sweeps every cell in 25 ticks without the snake ever meeting itself.
+        It stops at the third nugget, since that one brings an obstacle whose draws aren't replayed.
+        */
+        const SEED: u32 = 0x5EED;
+        let mut jungle = Jungle::new(Snake::new(), (3, 3), LcgRng::new(SEED));
+        let mut replay = LcgRng::new(SEED);
+        let mut scores = std::vec::Vec::new();
+
+        for tick in 0..100 {
+            let speed_nugget = jungle.speed_nugget;
+            let score = jungle.score;
+            jungle.update(Some(if tick % 5 == 4 { 'D' } else { 'R' }));
+            assert!(!jungle.game_over && !jungle.won);
+            if jungle.score == score {
+                continue;
+            }
+            scores.push(jungle.score);
+
+            // the nugget goes first, anywhere the grown snake and an older speed nugget aren't
+            let nugget = roll_free_cell(&mut replay, |x, y| {
+                !jungle.snake.occupies(x, y) && speed_nugget != Some((x as u8, y as u8))
+            });
+            assert_eq!(jungle.nugget, nugget, "nugget {}", jungle.score);
+
+            // then a one in SPEED_NUGGET_CHANCE roll for a speed nugget, if there isn't one out already
+            if speed_nugget.is_none() {
+                let expected = if replay.next_in_range(1, SPEED_NUGGET_CHANCE) == 1 {
+                    Some(roll_free_cell(&mut replay, |x, y| {
+                        !jungle.snake.occupies(x, y) && (x as u8, y as u8) != nugget
+                    }))
+                } else {
+                    None
+                };
+                assert_eq!(jungle.speed_nugget, expected, "speed nugget after nugget {}", jungle.score);
+            }
+
+            if jungle.score == 3 {
+                break;
+            }
+        }
+        assert_eq!(scores, [1, 2, 3]);
+    }
+
+    // how many games with random options the fuzzer plays, and how many frames each seed runs for
+    const FUZZ_SEEDS: u32 = 64;
+    const FUZZ_FRAMES: u32 = 2000;
+
+    fn fuzz_jungle(rng: &mut LcgRng) -> Jungle {
+        // a new game with its board options picked at random
+        let length = rng.next_in_range(1, 4) as usize;
+        let nugget = (rng.next_in_range(0, 4), rng.next_in_range(0, 4));
+        let mut jungle = Jungle::new(Snake::with_length(length).unwrap(), nugget, LcgRng::new(rng.next()));
+        for wraps in jungle.wrap.iter_mut() {
+            *wraps = rng.next_in_range(0, 1) == 1;
+        }
+        jungle.deadly_body = rng.next_in_range(0, 3) > 0;
+        jungle.growth_per_nugget = rng.next_in_range(1, 3);
+        jungle.reentry_gap = rng.next_in_range(0, 2) as u32;
+        jungle.set_nugget_count(rng.next_in_range(1, MAX_NUGGETS));
+        jungle
+    }
+
+    fn check_invariants(jungle: &Jungle, starting_length: usize) -> Result<(), std::string::String> {
+        // what has to hold after every frame: everything on the board, a body in one piece going
+        // only round the edges that wrap, as long as the nuggets eaten so far have made it, and a save that restores
+        let segments = &jungle.snake.segments;
+        if let Some(segment) = segments.iter().find(|segment| !in_bounds(segment.point.0, segment.point.1)) {
+            return Err(format!("segment off the board at {:?}", segment.point));
+        }
+        if !in_bounds(jungle.nugget.0 as i8, jungle.nugget.1 as i8) {
+            return Err(format!("nugget off the board at {:?}", jungle.nugget));
+        }
+        for pair in segments.windows(2) {
+            let (front, back) = (pair[0].point, pair[1].point);
+            if !['R', 'L', 'U', 'D'].iter().any(|&direction| step(back, direction, jungle.wrap) == Some(front)) {
+                return Err(format!("body broken between {:?} and {:?}", front, back));
+            }
+        }
+        // a snake crossing over itself can run out of segments before it's covered the board, and
+        // that's a win on the tick it eats with no room left to grow
+        let grown = jungle.nuggets_eaten as usize * jungle.growth_per_nugget as usize - jungle.growth_pending as usize;
+        let expected = (starting_length + grown).min(SNAKE_CAPACITY);
+        if segments.len() != expected && !jungle.won {
+            return Err(format!("length {} where {} was expected", segments.len(), expected));
+        }
+        if let Err(error) = Jungle::restore(&jungle.save_state()) {
+            return Err(format!("saved game doesn't restore: {:?}", error));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fuzzed_games_keep_their_invariants() {
+        // seeded random steering and dashing, over game after game with random board options, checked
+        // after every frame. A failure names the seed and frame, so it plays back the same every time
+        for seed in 1..=FUZZ_SEEDS {
+            let mut rng = LcgRng::new(seed);
+            let mut jungle = fuzz_jungle(&mut rng);
+            let mut starting_length = jungle.length();
+            for frame in 0..FUZZ_FRAMES {
+                let input = match rng.next_in_range(0, 7) {
+                    0 => Some('R'),
+                    1 => Some('L'),
+                    2 => Some('U'),
+                    3 => Some('D'),
+                    4 => {
+                        jungle.dash();
+                        None
+                    }
+                    _ => None,
+                };
+                let updated = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| jungle.update(input)));
+                assert!(updated.is_ok(), "seed {} frame {}: update panicked", seed, frame);
+                if let Err(problem) = check_invariants(&jungle, starting_length) {
+                    panic!("seed {} frame {}: {}", seed, frame, problem);
+                }
+
+                if jungle.game_over || jungle.won {
+                    jungle = fuzz_jungle(&mut rng);
+                    starting_length = jungle.length();
+                }
+            }
+        }
+    }
+}
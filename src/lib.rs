@@ -0,0 +1,6 @@
+#![cfg_attr(not(test), no_std)]
+
+pub mod controls;
+pub mod font;
+pub mod game;
+pub mod protocol;
@@ -3,15 +3,25 @@
 
 use cortex_m_rt::entry;
 use rtt_target::{rtt_init_print, rprintln};
-use heapless::Vec;
 use panic_rtt_target as _;
 use microbit::{
-    board::Board,
-    display::blocking::Display,
+    board::{Board, Buttons},
     hal::{prelude::*, Timer},
 };
 
 
+#[cfg(feature = "v1")]
+use microbit::{
+    hal::twi,
+    pac::twi0::frequency::FREQUENCY_A,
+};
+
+#[cfg(feature = "v1")]
+use microbit::{
+    hal::uart,
+    hal::uart::{Baudrate, Parity},
+};
+
 #[cfg(feature = "v2")]
 use microbit::{
     hal::twim,
@@ -20,7 +30,6 @@ use microbit::{
 
 #[cfg(feature = "v2")]
 use microbit::{
-    hal::prelude::*,
     hal::uarte,
     hal::uarte::{Baudrate, Parity},
 };
@@ -30,286 +39,549 @@ mod serial_setup;
 #[cfg(feature = "v2")]
 use serial_setup::UartePort;
 
+mod screen;
+use screen::{Screen, FRAME_MS, MAX_LEVEL};
+
+#[cfg(feature = "v2")]
+mod storage;
+#[cfg(feature = "v2")]
+use storage::Storage;
+
+#[cfg(feature = "v2")]
+mod sound;
+#[cfg(feature = "v2")]
+use sound::Speaker;
+
+use core::fmt::Write;
+use cortex_m::peripheral::NVIC;
+use microbit::hal::timer::Instance;
+use heapless::Vec;
 use lsm303agr::{
-    AccelOutputDataRate, Lsm303agr, MagOutputDataRate
+    interface::I2cInterface, mode::MagContinuous, AccelOutputDataRate, Lsm303agr, MagOutputDataRate
 };
+use snake::controls::{accel_magnitude, decode_key, detect_shake, mag_to_direction, parse_digits, rotate_direction, second_player_key, tilt_to_direction, ControlMode, KeyDecoderState, MomentumController, TiltAxes, TiltController};
+use snake::font::{text_frame, text_scroll_frames, DIGITS};
+use snake::protocol::{FrameDecoder, FRAME_START};
+use snake::game::{apply_command, autopilot_direction, dump_modes, render_ascii, seed_from_accel, speed_level, CollisionPolicy, DeathCause, GameEvent, Jungle, LcgRng, ScoreHistory, Snake, BOARD_SIZE, WRAP_ALL};
 
-struct LcgRng {
-    // pseudorandom number generator
-    state: u32,
-}
+// serial byte that moves the nugget somewhere harder to reach
+const REROLL_BYTE: u8 = b'X';
 
-impl LcgRng {
-    fn new(seed: u32) -> Self {
-        // seed is generated from accelerometer data, see below for more
-        LcgRng { state: seed }
-    }
+// serial byte that flips the display between normal and inverted
+const INVERT_BYTE: u8 = b'I';
 
-    fn next(&mut self) -> u8 {
-        // generates the next pseudorandom number
-        const MULTIPLIER: u32 = 1664525;
-        const INCREMENT: u32 = 1013904223;
-        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
-        self.state as u8
-    }
+// seed used when the accelerometer isn't there to provide one
+const FALLBACK_SEED: u32 = 0x5EED;
 
-    fn next_in_range(&mut self, min: u8, max: u8) -> u8 {
-        // takes the random number and puts it in bounds
-        (self.next() % (max - min + 1)) + min
-    }
-}
+// how many segments the snake hatches with, at most the width of the board
+const START_LENGTH: usize = 2;
 
+// which edges wrap round, top, bottom, left and right. False turns that edge into a wall,
+// all four false is classic snake
+const WRAP_EDGES: [bool; 4] = WRAP_ALL;
+// what running into the snake's own body does: End is game over, Shrink bites off the tail from there back
+const BODY_COLLISION: CollisionPolicy = CollisionPolicy::End;
 
-pub struct Jungle {
-    // captures all the relevant parts of the game
-    snake: Snake,  // fairly obvious, represents snake
-    basemap: [[u8; 5]; 5],  // represents the underlying grid that will be displayed
-    previous_direction: char, 
-    nugget: (u8, u8),  // snake's target
-    rng: LcgRng,  // pseudorandom number generator
-}
+// tick delay in kids mode, see lay_out_field
+const KIDS_TICK_MS: u32 = 900;
 
-impl Jungle {
-    pub fn new(snake: Snake, nugget: (u8, u8), rng: LcgRng) -> Self {
-        // initializes the jungle
-        Self {
-            basemap: [
-                [0, 0, 0, 0, 0],
-                [0, 0, 0, 0, 0],
-                [0, 0, 0, 0, 0],
-                [0, 0, 0, 0, 0],
-                [0, 0, 0, 0, 0],
-            ],
-            snake: snake,
-            previous_direction: 'R',
-            nugget: nugget,
-            rng: rng,
-        }
-    }
+// cells the snake has to wade through, taking an extra tick to leave, e.g. &[(2, 2)]
+const SLOW_CELLS: &[(u8, u8)] = &[];
 
-    pub fn update(&mut self, new_direction: Option<char>) {      
-        /*
-        Main driver of the game.
-        - iterate over each segment and update it
-        - check to see if the nugget was eaten
-        - if the nugget was eaten, append the segment correctly & generate a new one
-        - change direction of the snake if this was indicated
-        */
-        let optional_head = self.snake.segments.get(0).cloned();
-        let mut _new_direction : char;
-        self.basemap[self.nugget.0 as usize][self.nugget.1 as usize] = 1;
-
-        match optional_head {
-            Some(head) => {
-
-                let mut current_segment_index = 0;
-                let mut last_segment_clone = self.snake.segments.last().unwrap().clone();
-
-                while current_segment_index < self.snake.segments.len() {
-                    let current_segment = &mut self.snake.segments[current_segment_index];
-                    let current_segment_x = current_segment.point.0;
-                    let current_segment_y = current_segment.point.1;
-
-                    match new_direction {
-                        Some(_new_direction) => {
-                            if _new_direction != self.previous_direction && (
-                                // stupidity since sets are seemingly unusable?
-                                _new_direction == 'R' ||
-                                _new_direction == 'L' ||
-                                _new_direction == 'U' ||
-                                _new_direction == 'D'
-                            ) {
-                                current_segment.add_checkpoint(head.point.0, head.point.1, _new_direction);
-                            }
-                        },
-                        None => (),
-                    }
+// ticks the snake can go without eating before it starves, e.g. Some(60), None to never starve
+const STARVATION_TICKS: Option<u32> = None;
 
-                    // call update on the segment
-                    current_segment.update();
+// ticks a cell the tail left has to stay empty before the head may go back onto it, e.g. 2 against
+// tight spirals, 0 to let the head follow right behind the tail
+const REENTRY_GAP: u32 = 0;
 
-                    // if the segment has "eaten" the nugget, update snake accordingly 
-                    if current_segment.point.0 == self.nugget.0 as i8 && current_segment.point.1 == self.nugget.1 as i8 {
-                        let mut segment = push_segment_to_back(&last_segment_clone, last_segment_clone.default_direction);
-                        rprintln!("New segment: {}, {}, {}", segment.point.0, segment.point.1, segment.default_direction);
-                        self.snake.add_segment(segment);
+// how many times the whole board flashes when the snake dies
+const GAME_OVER_BLINKS: usize = 3;
+// before that, how many times the cell it ran into its own body on flashes, on the board as it ended
+const CRASH_FLASHES: usize = 3;
+// pattern left on the board after the flashing
+const GAME_OVER_CROSS: [[u8; 5]; 5] = [
+    [1, 0, 0, 0, 1],
+    [0, 1, 0, 1, 0],
+    [0, 0, 1, 0, 0],
+    [0, 1, 0, 1, 0],
+    [1, 0, 0, 0, 1],
+];
 
-                        self.nugget.0 = self.rng.next_in_range(0, 4);
-                        self.nugget.1 = self.rng.next_in_range(0, 4);
-                        rprintln!("New nugget: {}, {}", self.nugget.0, self.nugget.1);
-                    }
+// ticks the end of game screen stays up before the display goes dark and the core sleeps
+// between passes of the loop, still waking every FRAME_MS to look for a restart
+const END_SCREEN_TICKS: usize = 20;
+// SCR bit that lets a pending interrupt wake wfe, even one that's masked in the NVIC
+const SCB_SCR_SEVONPEND: u32 = 1 << 4;
 
-                    // TODO: death probably goes here!
-                    self.basemap[current_segment_x as usize][current_segment_y as usize] = 1;                    
-                    current_segment_index += 1;
-                }
-            },
-            None => ()
-        }
+// serial bytes that turn the whole display up or down a step, between 1 and MAX_LEVEL
+const BRIGHTER_BYTE: u8 = b'+';
+const DIMMER_BYTE: u8 = b'-';
 
-        // update direction based on input
-        match new_direction {
-            Some(_new_direction) => {
-                if _new_direction != self.previous_direction {
-                    self.previous_direction = _new_direction;
-                }
-            },
-            None => ()
+// how many ticks without any input before the snake starts playing itself
+const AUTOPILOT_IDLE_TICKS: u32 = 20;
+
+// steering over serial only, how many ticks without a byte before the terminal is taken
+// to be gone and the game pauses itself until it hears from it again. None leaves it running
+const SERIAL_LOST_TICKS: Option<u32> = None;
+
+// how the accelerometer is mounted, flip or swap axes here if tilting steers the wrong way
+const TILT_AXES: TiltAxes = TiltAxes::STANDARD;
+
+// serial byte that switches between steering by tilt and steering by pointing the board like a compass,
+// from any other control mode it goes to the compass
+const COMPASS_BYTE: u8 = b'M';
+
+// serial byte that makes the snake dash, two cells a tick for a few ticks. Button A does the same,
+// unless the buttons are what's steering
+const DASH_BYTE: u8 = b' ';
+
+// serial byte that freezes the game, and sent again carries on
+const PAUSE_BYTE: u8 = b'P';
+
+// serial byte that throws the current game away and starts a new one, pressing both buttons does the same
+const RESTART_BYTE: u8 = b'N';
+
+// serial byte that puts the mode flags and the speed dial back to how they were built, and starts a new game.
+// for when a few commands have left the game somewhere odd
+const DEFAULTS_BYTE: u8 = b'0';
+
+// serial byte that writes the current game to flash
+#[cfg(feature = "v2")]
+const SAVE_BYTE: u8 = b'V';
+// serial byte that starts typing a seed, ended with Enter, to replay a game exactly
+const SEED_BYTE: u8 = b'S';
+// serial byte that starts typing a command, ended with Enter, that changes a mode flag, see apply_command
+const COMMAND_BYTE: u8 = b':';
+// longest seed or command that can be typed, enough digits for any u32
+const LINE_MAX_LEN: usize = 10;
+// what terminals send for the backspace key, depending on how they're set up
+const BACKSPACE_BYTES: [u8; 2] = [0x08, 0x7F];
+// display frames a half received frame waits for its next byte before it's dropped, about half a second,
+// so a frame that lost a byte on the wire doesn't swallow the start of the next one
+const FRAME_TIMEOUT_FRAMES: u32 = 500 / FRAME_MS;
+
+// serial byte that asks for a binary snapshot of the board, see GameState::encode
+const SNAPSHOT_BYTE: u8 = b'G';
+
+// serial byte that prints the board as text, for a plain terminal
+const ASCII_BYTE: u8 = b'?';
+
+// serial byte that lists the scores of the last few games
+const HISTORY_BYTE: u8 = b'H';
+
+// serial byte that switches between one and two player games, starting a new one.
+// the second player steers with IJKL, the first keeps WASD and the arrows
+const TWO_PLAYER_BYTE: u8 = b'T';
+
+// serial byte that switches between endless games and time attacks, starting a new one
+const TIME_ATTACK_BYTE: u8 = b'Q';
+// how long a time attack lasts, in ticks, so it gets shorter as the snake speeds up
+const TIME_ATTACK_TICKS: u32 = 300;
+
+// accelerometer readings, one per display frame, looked at for a shake once the game is over
+const SHAKE_WINDOW: usize = 8;
+
+// how long the boot prompt waits for the player to accept a saved game
+#[cfg(feature = "v2")]
+const RESUME_PROMPT_MS: u32 = 3000;
+
+// the self test runs at boot with the selftest feature, or with button A held down.
+// it lights every led for a moment, then waits a while for a byte to echo back
+const SELF_TEST_LEDS_MS: u32 = 1000;
+const SELF_TEST_ECHO_MS: u32 = 5000;
+
+// holding button B at boot levels the tilt controls to whatever the board is resting on:
+// let go, and the accelerometer is averaged over this long with the centre led lit
+const CALIBRATION_MS: u32 = 1000;
+const CALIBRATION_FRAME: [[u8; 5]; 5] = [
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0],
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+];
+
+// start screen letters, in ControlMode::ALL order: S for serial, T tilt, M momentum, B buttons, C compass
+const CONTROL_MODE_ICONS: [[[u8; 5]; 5]; 5] = [
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    [
+        [1, 1, 1, 1, 1],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    [
+        [1, 0, 0, 0, 1],
+        [1, 1, 0, 1, 1],
+        [1, 0, 1, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+    ],
+    [
+        [0, 1, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [0, 1, 1, 0, 0],
+    ],
+    [
+        [0, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+    ],
+];
+// how long the start screen waits without any input before starting with the mode it shows
+const START_MENU_TIMEOUT_MS: u32 = 4000;
+
+// "3, 2, 1" shown before every game, one second each
+const COUNTDOWN_FROM: usize = 3;
+const COUNTDOWN_STEP_MS: u32 = 1000;
+// how long a scrolling number stays on each column
+const SCROLL_STEP_MS: u32 = 150;
+
+fn show_countdown(display: &mut Screen, timer: &mut Timer<DisplayTimer>, brightness: u8) {
+    // counts down before the snake starts moving, blocking until it's done
+    for digit in (1..=COUNTDOWN_FROM).rev() {
+        for _ in 0..COUNTDOWN_STEP_MS / FRAME_MS {
+            display.hold_levels(timer, full_levels(DIGITS[digit]), brightness);
         }
     }
 }
-pub struct Snake {
-    // represents snake, which is composed of "Segments"
-    segments: Vec<Segment, 25>,
-}
 
-impl Snake {
-    pub fn new() -> Self {
-        // initialize the snake with the head at (1, 1)
-        let mut body = Vec::new();
-        body.push(Segment {
-            point: (1,1),
-            default_direction: 'R',
-            checkpoints: Vec::new(),
-        });
-        body.push(Segment {
-            point: (1, 0),
-            default_direction: 'R',
-            checkpoints: Vec::new(),
-        });
-
-        Snake {
-            segments: body,
+fn scroll_text(
+    display: &mut Screen,
+    timer: &mut Timer<DisplayTimer>,
+    text: &[u8],
+    brightness: u8,
+    mut interrupted: impl FnMut() -> bool,
+) -> bool {
+    // scrolls text across the display from right to left, blocking until it's gone off the other side,
+    // or until interrupted says to stop. True if it was cut short
+    for offset in 0..text_scroll_frames(text) {
+        for _ in 0..SCROLL_STEP_MS / FRAME_MS {
+            if interrupted() {
+                return true;
+            }
+            display.hold_levels(timer, full_levels(text_frame(text, offset)), brightness);
         }
     }
+    false
+}
 
-    pub fn add_segment(&mut self, segment: Segment) {
-        // append new segment to the snake
-        self.segments.push(segment);
+pub fn game_over_frame(frame: usize) -> [[u8; 5]; 5] {
+    // nth frame of the death animation: the board blinks a few times, then settles on a cross
+    if frame >= GAME_OVER_BLINKS * 2 {
+        GAME_OVER_CROSS
+    } else if frame.is_multiple_of(2) {
+        [[1; 5]; 5]
+    } else {
+        [[0; 5]; 5]
     }
 }
 
-
-#[derive(Clone)]
-pub struct Segment {
-    /* 
-    Segment is the discrete element that makes up a snake.
-    - point indicates where the segment currently is
-    - default direction indicates which way the segment should be moving
-    - checkpoints is used to store the location of user-indicated turns
-    
-    Checkpoints are the secret sauce. This is how the snake "knows" when to
-    turn after the user has entered a turn.
-    */
-    point: (i8, i8),
-    default_direction: char,
-    checkpoints: Vec<(i8, i8, char), 10>,
+fn crash_frames(jungle: &Jungle) -> usize {
+    // how many frames of the death animation go to showing where the snake ran into itself, if it did
+    if jungle.crash_cell().is_some() { CRASH_FLASHES * 2 } else { 0 }
 }
 
-impl Segment {
-    pub fn add_checkpoint(&mut self, x: i8, y: i8, direction: char) {
-        // self explanatory, used to add a new checkpoint to the segment
-        self.checkpoints.push((x, y, direction));
+fn sleep_ms(timer: &mut Timer<DisplayTimer>, ms: u32) {
+    // waits like delay_ms, but with the core asleep instead of spinning. The timer's interrupt
+    // is never handled, it going pending is only there to wake wfe
+    timer.enable_interrupt();
+    timer.start(ms * 1_000);
+    while timer.wait().is_err() {
+        cortex_m::asm::wfe();
     }
+    timer.disable_interrupt();
+    NVIC::unpend(DisplayTimer::INTERRUPT);
+}
 
-    pub fn update(&mut self) {
-        // update each segment based on checkpoints
-        let current_checkpoint = self.checkpoints.get(0);
-        match current_checkpoint {
-            Some(value) => {
-                if (self.point.0 == value.0 && self.point.1 == value.1) {
-                    self.default_direction = value.2;
-                    self.checkpoints.remove(0);
-                }
-            },
-            None => ()
+fn full_levels(pattern: [[u8; 5]; 5]) -> [[u8; 5]; 5] {
+    // an on/off pattern as a frame of levels, every lit cell at full brightness
+    let mut frame = pattern;
+    for cell in frame.iter_mut().flat_map(|row| row.iter_mut()) {
+        if *cell != 0 {
+            *cell = MAX_LEVEL;
         }
+    }
+    frame
+}
 
-        // update point's location based on direction
-        if self.default_direction == 'R' {
-            self.point.1 += 1;
-            if self.point.1 == 5 {
-                self.point.1 = 0;
+pub fn won_frame(frame: usize) -> [[u8; 5]; 5] {
+    // nth frame of the victory animation: rings ripple out from the centre, over and over
+    let ring = frame % 3;
+    let mut pattern = [[0; 5]; 5];
+    for (x, row) in pattern.iter_mut().enumerate() {
+        for (y, cell) in row.iter_mut().enumerate() {
+            let distance = (x as i32 - 2).abs().max((y as i32 - 2).abs()) as usize;
+            if distance == ring {
+                *cell = 1;
             }
         }
+    }
+    pattern
+}
 
-        if self.default_direction == 'L' {
-            self.point.1 -= 1;
-            if self.point.1 == -1 {
-                self.point.1 = 4;
-            }
+fn lay_out_field(jungle: &mut Jungle) {
+    // applies the board options to a new game, a saved one keeps the ones it was started with
+    // pick which edges wrap and which kill
+    jungle.wrap = WRAP_EDGES;
+    // running into the body ends the game, or just bites the tail off
+    jungle.body_collision = BODY_COLLISION;
+    // how long it can go hungry
+    jungle.starvation_ticks = STARVATION_TICKS;
+    // and how soon it can go back over where it's been
+    jungle.reentry_gap = REENTRY_GAP;
+
+    // kids mode: the edges wrap, the snake crosses over itself instead of dying, and it
+    // roams at a slow pace that doesn't pick up as it grows. Obstacles are still deadly, and
+    // the speed dial and speed nuggets still work on top of the slower pace
+    if cfg!(feature = "kids") {
+        jungle.wrap = WRAP_ALL;
+        jungle.deadly_body = false;
+        jungle.fixed_tick_ms = Some(KIDS_TICK_MS);
+    }
+
+    // lay out the slow zones
+    for cell in SLOW_CELLS.iter() {
+        if !jungle.add_slow_cell(cell.0, cell.1) {
+            rprintln!("Too many slow cells, ignoring: {}, {}", cell.0, cell.1);
         }
+    }
+}
+
+// the v1 board has the older twi peripheral where the v2 has twim
+#[cfg(feature = "v1")]
+type I2cBus = twi::Twi<microbit::pac::TWI0>;
+#[cfg(feature = "v2")]
+type I2cBus = twim::Twim<microbit::pac::TWIM0>;
+
+type Accelerometer = Lsm303agr<I2cInterface<I2cBus>, MagContinuous>;
+
+// the display only ever waits a few ms at a time while the tick can run close to a second.
+// on v1 only TIMER0 counts past 16 bits, so there the two swap timers
+#[cfg(feature = "v1")]
+type DisplayTimer = microbit::pac::TIMER1;
+#[cfg(feature = "v2")]
+type DisplayTimer = microbit::pac::TIMER0;
 
-        if self.default_direction == 'U' {
-            self.point.0 -= 1;
-            if self.point.0 == -1 {
-                self.point.0 = 4;
+fn try_init_sensor(i2c: I2cBus) -> Option<Accelerometer> {
+    // initialization for accelerometer/magnet, None if anything on the i2c bus fails
+    let mut sensor = Lsm303agr::new_with_i2c(i2c);
+    sensor.init().ok()?;
+    sensor.set_accel_odr(AccelOutputDataRate::Hz50).ok()?;
+    sensor.set_mag_odr(MagOutputDataRate::Hz50).ok()?;
+    sensor.into_mag_continuous().ok()
+}
+
+fn self_test<S>(display: &mut Screen, timer: &mut Timer<DisplayTimer>, sensor: Option<&mut Accelerometer>, serial: &mut S)
+where
+    S: embedded_hal::serial::Read<u8> + embedded_hal::serial::Write<u8> + Write,
+{
+    // checks the board over before the game: every led, one reading from each sensor,
+    // and a byte echoed back over serial. Results go out over RTT
+    rprintln!("Self test");
+    for _ in 0..SELF_TEST_LEDS_MS / FRAME_MS {
+        display.hold_levels(timer, full_levels([[1; 5]; 5]), MAX_LEVEL);
+    }
+    display.clear();
+
+    match sensor {
+        Some(sensor) => {
+            match sensor.accel_data() {
+                Ok(data) => rprintln!("Accelerometer: x {} y {} z {}", data.x, data.y, data.z),
+                Err(_) => rprintln!("Accelerometer: read failed"),
+            }
+            match sensor.mag_data() {
+                Ok(data) => rprintln!("Magnetometer: x {} y {} z {}", data.x, data.y, data.z),
+                Err(_) => rprintln!("Magnetometer: read failed"),
             }
         }
+        None => rprintln!("Accelerometer and magnetometer: not found"),
+    }
 
-        if self.default_direction == 'D' {
-            self.point.0 += 1;
-            if self.point.0 == 5 {
-                self.point.0 = 0;
-            }
+    write!(serial, "Self test, press any key\r\n").ok();
+    let mut waited_ms = 0;
+    loop {
+        if let Ok(byte) = serial.read() {
+            nb::block!(serial.write(byte)).ok();
+            write!(serial, "\r\n").ok();
+            rprintln!("Serial: echoed {}", byte);
+            break;
+        }
+        if waited_ms >= SELF_TEST_ECHO_MS {
+            rprintln!("Serial: nothing came in");
+            break;
         }
+        timer.delay_ms(10_u32);
+        waited_ms += 10;
     }
+    rprintln!("Self test done");
 }
 
-pub fn push_segment_to_back(last_segment: &Segment, direction: char) -> Segment {
-    // find the location for the segment that will be appended
-    rprintln!("Incoming direction: {}", direction);
-    let mut new_segment = Segment {
-        point: (last_segment.point.0, last_segment.point.1),
-        default_direction: direction,
-        checkpoints: Vec::new(),
-    };
+fn calibrate(display: &mut Screen, timer: &mut Timer<DisplayTimer>, sensor: &mut Accelerometer) -> (i32, i32) {
+    // the average x and y the accelerometer reads with the board lying still, which tilt steering
+    // then treats as level. Nothing to go on if every reading failed, so no offset then
+    let mut sum = (0_i64, 0_i64);
+    let mut count = 0_i64;
+    for _ in 0..CALIBRATION_MS / FRAME_MS {
+        if let Ok(data) = sensor.accel_data() {
+            sum.0 += data.x as i64;
+            sum.1 += data.y as i64;
+            count += 1;
+        }
+        display.hold_levels(timer, full_levels(CALIBRATION_FRAME), MAX_LEVEL);
+    }
+    if count == 0 {
+        rprintln!("Calibration failed, no accelerometer readings");
+        return (0, 0);
+    }
+    let offset = ((sum.0 / count) as i32, (sum.1 / count) as i32);
+    rprintln!("Tilt offsets: x {} y {}, from {} readings", offset.0, offset.1, count);
+    offset
+}
 
-    // copy checkpoints from the last segment
-    new_segment.checkpoints.clone_from(&last_segment.checkpoints);
+fn choose_control_mode(
+    display: &mut Screen,
+    timer: &mut Timer<DisplayTimer>,
+    mut sensor: Option<&mut Accelerometer>,
+    buttons: &Buttons,
+    tilt_axes: TiltAxes,
+    brightness: u8,
+    default: ControlMode,
+) -> ControlMode {
+    // start screen: button A or tilting right steps to the next mode, tilting left to the one before,
+    // and button B picks the one showing. Left alone, it goes with the one showing, the default at first
+    let mut mode = default;
+    let mut idle_ms = 0;
+    let mut button_a_was_pressed = buttons.button_a.is_low().unwrap_or(false);
+    let mut button_b_was_pressed = buttons.button_b.is_low().unwrap_or(false);
+    let mut last_tilt: Option<char> = None;
+    while idle_ms < START_MENU_TIMEOUT_MS {
+        let icon = ControlMode::ALL.iter().position(|&option| option == mode).unwrap_or(0);
+        display.hold_levels(timer, full_levels(CONTROL_MODE_ICONS[icon]), brightness);
+        idle_ms += FRAME_MS;
 
-    // update segment based on direction, handle edge cases appropriately
-    if direction == 'R' {
-        new_segment.point.1 -= 1;
-        if new_segment.point.1 == -1 {
-            new_segment.point.1 = 4;
+        let button_a_pressed = buttons.button_a.is_low().unwrap_or(false);
+        let button_b_pressed = buttons.button_b.is_low().unwrap_or(false);
+        if button_b_pressed && !button_b_was_pressed {
+            break;
         }
-    }
 
-    if (direction == 'L') {
-        new_segment.point.1 += 1;
-        if new_segment.point.1 == 5 {
-            new_segment.point.1 = 0
+        // a tilt only steps once, the board has to come back level before it steps again
+        let tilt = sensor.as_mut().and_then(|sensor| sensor.accel_data().ok()).and_then(|data| {
+            let (x, y) = tilt_axes.apply(data.x, data.y);
+            tilt_to_direction(x, y)
+        });
+        let step = if button_a_pressed && !button_a_was_pressed {
+            Some(true)
+        } else if tilt != last_tilt {
+            match tilt {
+                Some('R') => Some(true),
+                Some('L') => Some(false),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        if let Some(forwards) = step {
+            mode = mode.cycle(forwards);
+            idle_ms = 0;
         }
+
+        button_a_was_pressed = button_a_pressed;
+        button_b_was_pressed = button_b_pressed;
+        last_tilt = tilt;
     }
+    rprintln!("Steering by {}", mode.name());
+    mode
+}
 
-    if direction == 'D' {
-        new_segment.point.0 -= 1;
-        if new_segment.point.0 == -1 {
-            new_segment.point.1 = 4;
+fn new_game(sensor: Option<&mut Accelerometer>, seed: Option<u32>, two_player: bool, time_attack: bool) -> Jungle {
+    // a fresh snake in a fresh jungle, shared by the first game and every restart
+    // a seed given here wins, otherwise read sensor data to get one, even a board lying flat jitters a little on every axis
+    let rng_seed = match (seed, sensor) {
+        (Some(seed), _) => seed,
+        (None, Some(sensor)) => {
+            let (x, y, z) = sensor.accel_data().map(|data| (data.x, data.y, data.z)).unwrap_or((0, 0, 0));
+            seed_from_accel(x, y, z)
         }
-    }
+        (None, None) => FALLBACK_SEED,
+    };
+    rprintln!("Seed: {}", rng_seed);
 
+    // intialize randomizer
+    let mut rng = LcgRng::new(rng_seed);
 
-    if direction == 'U' {
-        new_segment.point.0 += 1;
-        if new_segment.point.0 == 5 {
-            new_segment.point.0 = 0;
+    // randomly generate nugget coords
+    let random_x: u8 = rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+    let random_y: u8 = rng.next_in_range(0, BOARD_SIZE as u8 - 1);
+    rprintln!("Nugget x: {}", random_x);
+    rprintln!("Nugget y: {}", random_y);
+
+    // initialize snake in the jungle w/ a basemap & a nugget
+    let snake = Snake::with_length(START_LENGTH).unwrap_or_else(|| {
+        rprintln!("Snake can't start {} long, starting at the default length", START_LENGTH);
+        Snake::new()
+    });
+    let mut jungle = Jungle::new(snake, (random_x, random_y), rng);
+    lay_out_field(&mut jungle);
+
+    // the second player starts across the board, heading the other way
+    if two_player {
+        let rival = Snake::rival_with_length(START_LENGTH).unwrap_or_default();
+        if !jungle.add_rival(rival, 'L') {
+            rprintln!("No room for a second snake, playing alone");
         }
     }
-    return new_segment;
+
+    // a time attack is on the clock, shown round the edge of the board
+    if time_attack {
+        jungle.start_time_attack(TIME_ATTACK_TICKS);
+    }
+    jungle
 }
 
+fn acquire_board() -> Board {
+    // Board::take hands the peripherals out once and None after that. It's only ever called
+    // from here, and this only from the top of main, which the runtime enters exactly once and
+    // never returns from, so the expect can't fire unless a second caller is added
+    Board::take().expect("board peripherals already taken, acquire_board must only run once")
+}
 
 #[entry]
 fn main() -> ! {
     // initialize board elements
     rtt_init_print!();
-    let board = microbit::Board::take().unwrap();
-    let mut timer = Timer::new(board.TIMER0);
-    let mut display = Display::new(board.display_pins);
+    let board = acquire_board();
+    #[cfg(feature = "v1")]
+    let (display_timer, tick_timer) = (board.TIMER1, board.TIMER0);
+    #[cfg(feature = "v2")]
+    let (display_timer, tick_timer) = (board.TIMER0, board.TIMER1);
+    let mut timer = Timer::new(display_timer);
+    #[cfg(not(feature = "nonblocking-display"))]
+    let mut display = Screen::new(board.display_pins);
+    #[cfg(feature = "nonblocking-display")]
+    let mut display = Screen::new(board.display_pins, board.TIMER2);
+
+    // lets sleep_ms wake on its timer without an interrupt handler
+    let scb = board.SCB;
+    unsafe { scb.scr.modify(|scr| scr | SCB_SCR_SEVONPEND) };
 
     // initialize serial interface
+    #[cfg(feature = "v1")]
+    let mut serial = uart::Uart::new(board.UART0, board.uart.into(), Parity::EXCLUDED, Baudrate::BAUD115200);
+
     #[cfg(feature = "v2")]
     let mut serial = {
         let serial = uarte::Uarte::new(
@@ -321,74 +593,527 @@ fn main() -> ! {
         UartePort::new(serial)
     };
 
+    #[cfg(feature = "v1")]
+    let i2c = { twi::Twi::new(board.TWI0, board.i2c.into(), FREQUENCY_A::K100) };
+
     #[cfg(feature = "v2")]
-    let mut i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
+    let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
 
-    // initialization for accelerometer/magnet
-    let mut sensor = Lsm303agr::new_with_i2c(i2c);
-    sensor.init().unwrap();
- 
-    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
-    sensor.set_mag_odr(MagOutputDataRate::Hz50).unwrap();
+    // without the sensor the game still runs, on a fixed seed and steered over serial and the buttons
+    let mut sensor = try_init_sensor(i2c);
+    if sensor.is_none() {
+        rprintln!("Warning: accelerometer didn't come up, tilt and compass steering are off");
+    }
 
-    let mut sensor = sensor.into_mag_continuous().ok().unwrap();
+    // make sure the board works before blaming the game
+    let buttons = board.buttons;
+    if cfg!(feature = "selftest") || buttons.button_a.is_low().unwrap_or(false) {
+        self_test(&mut display, &mut timer, sensor.as_mut(), &mut serial);
+    }
 
-    // read sensor data to get seed
-    let mut sensor_data = sensor.accel_data().unwrap();
-    let mut seed = sensor_data.y as u32;
+    // level the tilt controls if asked, once button B has been let go so the board is resting
+    let mut tilt_axes = TILT_AXES;
+    if buttons.button_b.is_low().unwrap_or(false) {
+        if let Some(sensor) = sensor.as_mut() {
+            while buttons.button_b.is_low().unwrap_or(false) {
+                display.hold_levels(&mut timer, full_levels(CALIBRATION_FRAME), MAX_LEVEL);
+            }
+            tilt_axes = TILT_AXES.with_offset(calibrate(&mut display, &mut timer, sensor));
+        }
+    }
 
-    // intialize randomizer
-    let mut rng = LcgRng::new(seed);
+    // onboard speaker for sound effects
+    #[cfg(feature = "v2")]
+    let mut speaker = Speaker::new(board.speaker_pin, board.TIMER3);
 
-    // randomly generate nugget coords
-    let random_x: u8 = rng.next_in_range(0, 4);
-    let random_y: u8 = rng.next_in_range(0, 4);
-    rprintln!("Nugget x: {}", random_x);
-    rprintln!("Nugget y: {}", random_y);
+    // set up the first game
+    let mut jungle: Jungle = new_game(sensor.as_mut(), None, false, false);
 
-    // initialize snake in the jungle w/ a basemap & a nugget
-    let mut nugget: (u8, u8) = (random_x, random_y);
-    let mut snake = Snake::new();
-    let mut jungle: Jungle = Jungle::new(snake, nugget, rng);
-    let mut basemap: [[u8; 5]; 5] = [
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-    ];
+    // offer to pick up where the last saved game left off
+    #[cfg(feature = "v2")]
+    let mut storage = {
+        // the board doesn't hand out the flash controller, so take it directly
+        let peripherals = unsafe { microbit::pac::Peripherals::steal() };
+        Storage::new(peripherals.NVMC)
+    };
+
+    // best score from earlier sessions, kept in its own flash page
+    #[cfg(feature = "v2")]
+    let mut high_score = storage.load_high_score();
+    #[cfg(feature = "v2")]
+    rprintln!("High score: {}", high_score);
+
+    #[cfg(feature = "v2")]
+    if let Ok(saved) = Jungle::restore(&storage.read_state()) {
+        rprintln!("Saved game found, press 'y' to resume");
+        write!(serial, "Saved game found, press 'y' to resume\r\n").ok();
+
+        let mut waited_ms = 0;
+        while waited_ms < RESUME_PROMPT_MS {
+            match serial.read() {
+                Ok(b'y') | Ok(b'Y') => {
+                    // it comes back with the board it was saved with, so lay_out_field isn't needed,
+                    // and would add the slow cells a second time
+                    jungle = saved;
+                    rprintln!("Resumed saved game");
+                    break;
+                }
+                Ok(_) => break,
+                Err(_) => {}
+            }
+            timer.delay_ms(10_u32);
+            waited_ms += 10;
+        }
+    }
 
     // set initial conditions
-    let mut previous_snake_direction : char = 'R';
+    let mut end_frames_shown: usize = 0;
+    let mut brightness: u8 = MAX_LEVEL;
+
+    // pick what steers, tilting needs the sensor so without it the buttons are the default
+    let default_mode = if sensor.is_some() { ControlMode::Tilt } else { ControlMode::Buttons };
+    let mut control_mode = choose_control_mode(&mut display, &mut timer, sensor.as_mut(), &buttons, tilt_axes, brightness, default_mode);
+    let mut button_a_was_pressed = buttons.button_a.is_low().unwrap_or(false);
+    let mut button_b_was_pressed = buttons.button_b.is_low().unwrap_or(false);
+
+    // give the player a moment to get ready
+    show_countdown(&mut display, &mut timer, brightness);
+
+    // the game advances on its own timer while input is polled continuously,
+    // a little faster every time the snake grows
+    let mut tick_ms = jungle.tick_ms();
+    let mut tick_timer = Timer::periodic(tick_timer);
+    tick_timer.start(tick_ms * 1_000);
+    let mut idle_ticks: u32 = 0;
+    let mut key_state = KeyDecoderState::Idle;
+    let mut tilt = TiltController::with_axes(tilt_axes);
+    let mut momentum = MomentumController::with_axes(tilt_axes);
+    // a resumed game comes back in the modes it was saved in
+    let mut two_player = jungle.has_rival();
+    let mut time_attack = jungle.ticks_remaining().is_some();
+    let mut rival_direction: Option<char> = None;
+    let mut shake_samples: Vec<i32, SHAKE_WINDOW> = Vec::new();
+    let mut score_history = ScoreHistory::new();
+    // the byte that started the line being typed, and what's been typed so far
+    let mut typed_line: Option<(u8, Vec<u8, LINE_MAX_LEN>)> = None;
+    let mut ticks_since_input: u32 = 0;
+    let mut lost_serial_pause = false;
+    let mut frames = FrameDecoder::new();
+    let mut frame_wait_frames: u32 = 0;
+    // the GAME OVER scroll was cut short, which starts a new game
+    let mut restart_after_scroll = false;
 
     loop {
-        // read direction
-        let serial_byte = serial.read();
-        let mut snake_direction: Option<char> = None;
-
-        match serial_byte {
-            Ok(x) => {
-                snake_direction = Some(x as char);
-                rprintln!("Snake direction: {}", x as char);
+        let mut restart = core::mem::take(&mut restart_after_scroll);
+        let mut forced_seed: Option<u32> = None;
+        let mut defaults = false;
+        // the display and anything counted in frames only move on once a frame. The blocking display spends
+        // every pass showing one, the interrupt driven one leaves the loop free to poll input in between
+        let frame_due = display.frame_due(&mut timer);
+
+        // read everything that has come in since the last pass, one byte a pass drops keys typed or pasted quickly.
+        // turns are queued up and taken one per tick, and anything after a restart waits for the new game
+        loop {
+            let mut serial_byte = serial.read();
+            let byte_read = serial_byte.is_ok();
+
+            // anything at all from serial means the terminal is still there
+            if serial_byte.is_ok() {
+                ticks_since_input = 0;
             }
-            Err(_) => {},
+
+            /*
+            Host tools can send commands framed, see snake::protocol, alongside raw bytes from a terminal.
+            - the bytes of a frame aren't handled on their own, only the command once the whole frame checks out
+            - a good frame stands in for its command byte, and throws away anything half typed
+            - a seed or command carried in the payload is taken as if it had been typed and Enter pressed
+            - a frame sent in one burst is fed through whole in the same pass, since every waiting byte is read
+            */
+            match serial_byte {
+                Ok(byte) if byte == FRAME_START || frames.in_frame() => {
+                    frame_wait_frames = 0;
+                    serial_byte = Err(nb::Error::WouldBlock);
+                    if let Some(command) = frames.feed(byte) {
+                        typed_line = None;
+                        serial_byte = Ok(command.code);
+                        if (command.code == SEED_BYTE || command.code == COMMAND_BYTE) && !command.payload.is_empty() {
+                            typed_line = Vec::from_slice(&command.payload).ok().map(|line| (command.code, line));
+                            serial_byte = Ok(b'\r');
+                        }
+                    } else if !frames.in_frame() {
+                        rprintln!("Bad frame dropped");
+                    }
+                }
+                _ => {}
+            }
+
+            // a P that comes in first resumes by toggling the pause itself
+            if serial_byte.is_ok() && lost_serial_pause {
+                lost_serial_pause = false;
+                if !matches!(serial_byte, Ok(PAUSE_BYTE)) {
+                    jungle.paused = false;
+                    rprintln!("Serial is back, resumed");
+                }
+            }
+
+            match serial_byte {
+                Ok(byte) if typed_line.is_some() => {
+                    // a seed or a command is being typed, everything up to Enter belongs to it
+                    let (kind, line) = typed_line.as_mut().unwrap();
+                    if byte == b'\r' || byte == b'\n' {
+                        if *kind == SEED_BYTE {
+                            match parse_digits(line) {
+                                Some(seed) => {
+                                    forced_seed = Some(seed);
+                                    restart = true;
+                                }
+                                None => rprintln!("Not a seed, keeping the current game"),
+                            }
+                        } else {
+                            let command = core::str::from_utf8(line).unwrap_or("");
+                            if apply_command(&mut jungle, command) {
+                                rprintln!("Command: {}", command);
+                                write!(serial, "\r\nok\r\n").ok();
+                            } else {
+                                rprintln!("Unknown command: {}", command);
+                                write!(serial, "\r\nunknown command\r\n").ok();
+                            }
+                        }
+                        typed_line = None;
+                    } else if BACKSPACE_BYTES.contains(&byte) {
+                        // rub the last character out on the terminal as well, if there was one
+                        if line.pop().is_some() {
+                            write!(serial, "\x08 \x08").ok();
+                        }
+                    } else if line.push(byte).is_err() {
+                        rprintln!("Too long, dropped what was typed");
+                        typed_line = None;
+                    } else if byte.is_ascii_graphic() {
+                        // typed characters come back so nothing is typed blind
+                        nb::block!(serial.write(byte)).ok();
+                    }
+                }
+                Ok(byte @ b'1'..=b'9') => {
+                    // digits set the speed dial, 5 is the normal curve
+                    let setting = jungle.set_speed_setting(byte - b'0');
+                    rprintln!("Speed setting {}: {}ms per tick", setting, jungle.tick_ms());
+                }
+                Ok(SEED_BYTE) => {
+                    typed_line = Some((SEED_BYTE, Vec::new()));
+                    rprintln!("Type a seed and press Enter");
+                }
+                Ok(COMMAND_BYTE) => {
+                    typed_line = Some((COMMAND_BYTE, Vec::new()));
+                    nb::block!(serial.write(COMMAND_BYTE)).ok();
+                }
+                Ok(INVERT_BYTE) => {
+                    jungle.invert_display = !jungle.invert_display;
+                }
+                Ok(REROLL_BYTE) => {
+                    if jungle.reroll_nugget() {
+                        rprintln!("Nugget moved to: {}, {}, score: {}", jungle.nugget.0, jungle.nugget.1, jungle.score);
+                    } else {
+                        rprintln!("No rerolls left for this nugget");
+                    }
+                }
+                Ok(COMPASS_BYTE) => {
+                    control_mode = if control_mode == ControlMode::Compass { ControlMode::Tilt } else { ControlMode::Compass };
+                    rprintln!("Steering by {}", control_mode.name());
+                }
+                Ok(DASH_BYTE) => {
+                    if jungle.dash() {
+                        rprintln!("Dash");
+                    }
+                }
+                Ok(PAUSE_BYTE) => {
+                    jungle.paused = !jungle.paused;
+                    rprintln!("{}", if jungle.paused { "Paused" } else { "Resumed" });
+                }
+                Ok(RESTART_BYTE) => {
+                    restart = true;
+                }
+                Ok(DEFAULTS_BYTE) => {
+                    restart = true;
+                    defaults = true;
+                }
+                #[cfg(feature = "v2")]
+                Ok(SAVE_BYTE) => {
+                    if storage.write_state(&jungle.save_state()) {
+                        rprintln!("Game saved");
+                    } else {
+                        rprintln!("Saving game failed");
+                    }
+                }
+                Ok(BRIGHTER_BYTE) => {
+                    brightness = (brightness + 1).min(MAX_LEVEL);
+                    rprintln!("Brightness: {}", brightness);
+                }
+                Ok(DIMMER_BYTE) => {
+                    brightness = (brightness - 1).max(1);
+                    rprintln!("Brightness: {}", brightness);
+                }
+                Ok(TWO_PLAYER_BYTE) => {
+                    two_player = !two_player;
+                    restart = true;
+                    rprintln!("{} player game", if two_player { "Two" } else { "One" });
+                }
+                Ok(TIME_ATTACK_BYTE) => {
+                    time_attack = !time_attack;
+                    restart = true;
+                    rprintln!("{}", if time_attack { "Time attack" } else { "Endless game" });
+                }
+                Ok(HISTORY_BYTE) => {
+                    score_history.dump(&mut serial).ok();
+                }
+                Ok(ASCII_BYTE) => {
+                    render_ascii(&jungle, &mut serial).ok();
+                }
+                Ok(SNAPSHOT_BYTE) => {
+                    for byte in jungle.snapshot().encode().iter() {
+                        nb::block!(serial.write(*byte)).ok();
+                    }
+                }
+                Ok(x) => {
+                    if let Some(direction) = second_player_key(x).filter(|_| two_player) {
+                        // the second snake turns on the next tick, the last key before it wins
+                        rival_direction = Some(direction);
+                    } else if let Some(direction) = decode_key(x, &mut key_state) {
+                        if jungle.queue_direction(direction) {
+                            rprintln!("Snake direction: {}", direction);
+                        }
+                    }
+                }
+                Err(_) => {},
+            }
+
+            if !byte_read || restart {
+                break;
+            }
+        }
+        if frame_due && frames.in_frame() {
+            frame_wait_frames += 1;
+            if frame_wait_frames >= FRAME_TIMEOUT_FRAMES {
+                frames.reset();
+                rprintln!("Frame cut short, dropped");
+            }
+        }
+
+        // buttons turn the snake a quarter turn: A counter-clockwise, B clockwise.
+        // when they're not steering A dashes instead. Both together restart, whatever is steering
+        let button_a_pressed = buttons.button_a.is_low().unwrap_or(false);
+        let button_b_pressed = buttons.button_b.is_low().unwrap_or(false);
+        let button_steering = control_mode == ControlMode::Buttons;
+        if button_a_pressed && button_b_pressed && !(button_a_was_pressed && button_b_was_pressed) {
+            restart = true;
+        } else if button_steering && button_a_pressed && !button_a_was_pressed {
+            jungle.queue_direction(rotate_direction(jungle.intended_heading(), false));
+        } else if button_steering && button_b_pressed && !button_b_was_pressed {
+            jungle.queue_direction(rotate_direction(jungle.intended_heading(), true));
+        } else if !button_steering && button_a_pressed && !button_a_was_pressed && jungle.dash() {
+            rprintln!("Dash");
+        }
+        button_a_was_pressed = button_a_pressed;
+        button_b_was_pressed = button_b_pressed;
+
+        // once the game is over, shaking the board starts a new one
+        if frame_due && (jungle.game_over || jungle.won) {
+            if let Some(Ok(data)) = sensor.as_mut().map(|sensor| sensor.accel_data()) {
+                let magnitude = accel_magnitude(data.x, data.y, data.z);
+                if shake_samples.is_full() {
+                    shake_samples.rotate_left(1);
+                    shake_samples.pop();
+                }
+                shake_samples.push(magnitude).ok();
+                if shake_samples.is_full() && detect_shake(&shake_samples) {
+                    rprintln!("Shaken");
+                    restart = true;
+                }
+            }
+        }
+
+        // start over with a freshly seeded game, or the one that was just typed in
+        if restart {
+            shake_samples.clear();
+            let speed_setting = jungle.speed_setting();
+            jungle = new_game(sensor.as_mut(), forced_seed, two_player, time_attack);
+            if defaults {
+                // a new game already starts on the built-in flags, only the speed dial carries over otherwise
+                let mut modes: heapless::String<24> = heapless::String::new();
+                dump_modes(&jungle, &mut modes).ok();
+                rprintln!("Back to defaults: {}", modes);
+                write!(serial, "\r\ndefaults: {}\r\n", modes).ok();
+            } else {
+                jungle.set_speed_setting(speed_setting);
+            }
+            end_frames_shown = 0;
+            ticks_since_input = 0;
+            lost_serial_pause = false;
+            show_countdown(&mut display, &mut timer, brightness);
+            tick_ms = jungle.tick_ms();
+            tick_timer.start(tick_ms * 1_000);
+            rprintln!("New game");
+        }
+
+        // keep the display lit between ticks, with the blocking display this is also what paces the loop.
+        // a game that ended a while ago goes dark and sleeps instead, for the battery's sake
+        let ended = jungle.game_over || jungle.won;
+        if frame_due {
+            if ended && end_frames_shown >= END_SCREEN_TICKS {
+                display.clear();
+                sleep_ms(&mut timer, FRAME_MS);
+            } else if jungle.game_over && end_frames_shown < crash_frames(&jungle) {
+                display.show_levels(&mut timer, jungle.crash_frame(end_frames_shown), brightness);
+            } else if jungle.game_over {
+                display.show_levels(&mut timer, full_levels(game_over_frame(end_frames_shown - crash_frames(&jungle))), brightness);
+            } else if jungle.won {
+                display.show_levels(&mut timer, full_levels(won_frame(end_frames_shown)), brightness);
+            } else {
+                display.show_levels(&mut timer, jungle.render(), brightness);
+            }
+        }
+
+        // only advance the game once a whole tick has passed
+        if tick_timer.wait().is_err() {
+            continue;
+        }
+
+        // once the game has ended, step through the death or victory animation instead
+        if jungle.game_over || jungle.won {
+            end_frames_shown = end_frames_shown.saturating_add(1);
+            // once the board has finished blinking, GAME OVER and the score go by before it settles on the cross.
+            // Any byte or button press on the way skips the rest and starts a new game
+            if jungle.game_over && end_frames_shown == crash_frames(&jungle) + GAME_OVER_BLINKS * 2 {
+                let mut text: heapless::String<24> = heapless::String::new();
+                write!(text, "GAME OVER {}", jungle.score).ok();
+                let mut buttons_were_pressed = button_a_was_pressed || button_b_was_pressed;
+                restart_after_scroll = scroll_text(&mut display, &mut timer, text.as_bytes(), brightness, || {
+                    let buttons_pressed = buttons.button_a.is_low().unwrap_or(false) || buttons.button_b.is_low().unwrap_or(false);
+                    let pressed = buttons_pressed && !buttons_were_pressed;
+                    buttons_were_pressed = buttons_pressed;
+                    serial.read().is_ok() || pressed
+                });
+            }
+            continue;
+        }
+
+        // a serial player who has gone quiet for too long has probably lost the connection
+        ticks_since_input = ticks_since_input.saturating_add(1);
+        if let Some(limit) = SERIAL_LOST_TICKS {
+            if control_mode == ControlMode::Serial && !jungle.paused && ticks_since_input >= limit {
+                jungle.paused = true;
+                lost_serial_pause = true;
+                rprintln!("Nothing from serial for {} ticks, paused", limit);
+            }
+        }
+
+        // a paused game keeps its board on the display until it's resumed
+        if jungle.paused {
+            continue;
         }
 
-        // render the snake in the jungle
-        jungle.update(snake_direction);
-        display.show(&mut timer, jungle.basemap, 175);
+        // with nothing from serial, steer by tilting or pointing the board if that's the control mode
+        let mut pending_direction: Option<char> = None;
+        match sensor.as_mut() {
+            Some(sensor) if !jungle.has_queued_turns() => match control_mode {
+                ControlMode::Compass => {
+                    if let Ok(data) = sensor.mag_data() {
+                        pending_direction = Some(mag_to_direction(data.x, data.y));
+                    }
+                }
+                ControlMode::Tilt => {
+                    if let Ok(data) = sensor.accel_data() {
+                        pending_direction = tilt.update(data.x, data.y);
+                    }
+                }
+                ControlMode::Momentum => {
+                    if let Ok(data) = sensor.accel_data() {
+                        pending_direction = momentum.update(data.x, data.y);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
 
-        // clear the basemap
-        jungle.basemap = [
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0],
-        ];
+        // left alone for long enough, the snake plays itself until any real input comes in
+        if pending_direction.is_some() || jungle.has_queued_turns() {
+            if idle_ticks >= AUTOPILOT_IDLE_TICKS {
+                rprintln!("Demo mode off");
+            }
+            idle_ticks = 0;
+        } else {
+            idle_ticks = idle_ticks.saturating_add(1);
+            if idle_ticks == AUTOPILOT_IDLE_TICKS {
+                rprintln!("Demo mode on");
+            }
+            if idle_ticks >= AUTOPILOT_IDLE_TICKS {
+                pending_direction = Some(autopilot_direction(&jungle));
+            }
+        }
+
+        // move the snake in the jungle
+        let previous_scores = (jungle.score, jungle.rival_score);
+        let event = jungle.update_both(pending_direction, rival_direction.take());
 
-        // delay for aesthetics
-        timer.delay_ms(500_u32);
+        // beep about whatever just happened
+        #[cfg(feature = "v2")]
+        if let Some(event) = event {
+            speaker.play(event);
+        }
 
+        // or just click in time with the moves, to help time the turns
+        #[cfg(all(feature = "v2", feature = "metronome"))]
+        if event.is_none() && !jungle.paused && !jungle.game_over && !jungle.won {
+            speaker.click(tick_ms);
+        }
+
+        // let the host terminal know how it's going
+        if (jungle.score, jungle.rival_score) != previous_scores {
+            if two_player {
+                write!(serial, "Score: {} - {}\r\n", jungle.score, jungle.rival_score).ok();
+            } else {
+                write!(serial, "Score: {}\r\n", jungle.score).ok();
+            }
+        }
+
+        // one machine readable status line per tick: tick, head, length, nugget, score
+        #[cfg(feature = "telemetry")]
+        {
+            let head = jungle.head();
+            write!(
+                serial,
+                "T={} H={},{} L={} N={},{} S={}\r\n",
+                jungle.ticks(), head.0, head.1, jungle.length(), jungle.nugget.0, jungle.nugget.1, jungle.score
+            ).ok();
+        }
+
+        // sum the game up on the tick it ends
+        if event.is_some() && (jungle.game_over || jungle.won) {
+            score_history.push(jungle.score);
+            let ending = if event == Some(GameEvent::TimeUp) { "Time up" } else { "Game over" };
+            let cause = jungle.death_cause().map_or("board full", DeathCause::describe);
+            rprintln!("{} ({}): score {}, {} ticks, {}s", ending, cause, jungle.score, jungle.ticks(), jungle.elapsed_secs());
+            write!(serial, "{} ({}): score {}, {} ticks, {}s\r\n", ending, cause, jungle.score, jungle.ticks(), jungle.elapsed_secs()).ok();
+        }
+
+        // remember a new record once the game is over
+        #[cfg(feature = "v2")]
+        if (jungle.game_over || jungle.won) && jungle.score > high_score {
+            high_score = jungle.score;
+            if storage.save_high_score(high_score) {
+                rprintln!("New high score: {}", high_score);
+                write!(serial, "New high score: {}\r\n", high_score).ok();
+            } else {
+                rprintln!("Saving high score failed");
+            }
+        }
+
+        // speed up for the next tick if the snake got longer or a boost started or ran out
+        let next_tick_ms = jungle.tick_ms();
+        if next_tick_ms != tick_ms {
+            tick_ms = next_tick_ms;
+            tick_timer.start(tick_ms * 1_000);
+            rprintln!("Speed level {}: {}ms per tick", speed_level(tick_ms), tick_ms);
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,191 @@
+use heapless::Vec;
+
+// first byte of a frame, STX, which nothing typed at a terminal sends
+pub const FRAME_START: u8 = 0x02;
+// most payload bytes a frame can carry, enough for a typed seed or command
+pub const MAX_PAYLOAD: usize = 10;
+// start, command, length, payload and checksum
+pub const MAX_FRAME_LEN: usize = MAX_PAYLOAD + 4;
+
+/*
+A command from a host tool, checked end to end.
+- code is the same byte that would be sent raw, so a framed b'P' pauses just like a P does
+- payload is what would otherwise be typed after it, the digits after S or the command after :
+*/
+#[derive(Debug, PartialEq)]
+pub struct Command {
+    pub code: u8,
+    pub payload: Vec<u8, MAX_PAYLOAD>,
+}
+
+// where we are in a frame: START, command, payload length, the payload, then the XOR of everything after START
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FrameState {
+    Idle,
+    Code,
+    Length(u8),
+    Payload(u8, u8),
+    Checksum(u8),
+}
+
+pub struct FrameDecoder {
+    state: FrameState,
+    payload: Vec<u8, MAX_PAYLOAD>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> FrameDecoder {
+        FrameDecoder { state: FrameState::Idle, payload: Vec::new() }
+    }
+
+    pub fn in_frame(&self) -> bool {
+        // a START has come in and the frame it began isn't finished yet
+        self.state != FrameState::Idle
+    }
+
+    pub fn reset(&mut self) {
+        // drops whatever of a frame has come in, the next byte has to be a START again
+        self.state = FrameState::Idle;
+        self.payload.clear();
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<Command> {
+        // takes the next byte off the wire, and hands back the command once a whole frame has checked out.
+        // outside a frame anything but START is left for the raw byte handling, and is ignored here
+        match self.state {
+            FrameState::Idle => {
+                if byte == FRAME_START {
+                    self.payload.clear();
+                    self.state = FrameState::Code;
+                }
+            }
+            FrameState::Code => {
+                self.state = FrameState::Length(byte);
+            }
+            FrameState::Length(code) => {
+                self.state = match byte as usize {
+                    0 => FrameState::Checksum(code),
+                    length if length <= MAX_PAYLOAD => FrameState::Payload(code, byte),
+                    // longer than anything we send, it's corrupt
+                    _ => FrameState::Idle,
+                };
+            }
+            FrameState::Payload(code, length) => {
+                self.payload.push(byte).ok();
+                if self.payload.len() == length as usize {
+                    self.state = FrameState::Checksum(code);
+                }
+            }
+            FrameState::Checksum(code) => {
+                self.state = FrameState::Idle;
+                let payload = core::mem::take(&mut self.payload);
+                if byte == checksum(code, &payload) {
+                    return Some(Command { code, payload });
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        FrameDecoder::new()
+    }
+}
+
+fn checksum(code: u8, payload: &[u8]) -> u8 {
+    // XOR of the command, the payload length and every payload byte
+    payload.iter().fold(code ^ payload.len() as u8, |sum, byte| sum ^ byte)
+}
+
+pub fn encode_frame(code: u8, payload: &[u8]) -> Option<Vec<u8, MAX_FRAME_LEN>> {
+    // the bytes a host sends for a command, None if the payload is too long to fit in a frame
+    if payload.len() > MAX_PAYLOAD {
+        return None;
+    }
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&[FRAME_START, code, payload.len() as u8]).ok()?;
+    frame.extend_from_slice(payload).ok()?;
+    frame.push(checksum(code, payload)).ok()?;
+    Some(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(decoder: &mut FrameDecoder, bytes: &[u8]) -> std::vec::Vec<Command> {
+        bytes.iter().filter_map(|&byte| decoder.feed(byte)).collect()
+    }
+
+    #[test]
+    fn frames_decode_to_commands() {
+        let mut decoder = FrameDecoder::new();
+        let pause = encode_frame(b'P', b"").unwrap();
+        assert_eq!(pause.as_slice(), [FRAME_START, b'P', 0, b'P']);
+        assert_eq!(feed_all(&mut decoder, &pause), [Command { code: b'P', payload: Vec::new() }]);
+
+        // only the last byte finishes it, and it's idle again straight after
+        let command = encode_frame(b':', b"W0").unwrap();
+        for &byte in &command[..command.len() - 1] {
+            assert_eq!(decoder.feed(byte), None);
+            assert!(decoder.in_frame());
+        }
+        let decoded = decoder.feed(command[command.len() - 1]).unwrap();
+        assert_eq!(decoded.code, b':');
+        assert_eq!(decoded.payload.as_slice(), b"W0");
+        assert!(!decoder.in_frame());
+
+        assert!(encode_frame(b'S', b"12345678901").is_none());
+    }
+
+    #[test]
+    fn raw_bytes_outside_a_frame_are_left_alone() {
+        let mut decoder = FrameDecoder::new();
+        assert!(feed_all(&mut decoder, b"wasdP\x1b[A").is_empty());
+        assert!(!decoder.in_frame());
+    }
+
+    #[test]
+    fn back_to_back_frames_all_decode() {
+        // a host sends its frames in one burst with nothing in between, and every one comes out in order
+        let mut decoder = FrameDecoder::new();
+        let frames: [(u8, &[u8]); 3] = [(b'S', b"1234567890"), (b'P', b""), (b':', b"W0")];
+        let mut burst = std::vec::Vec::new();
+        for (code, payload) in frames.iter() {
+            burst.extend_from_slice(&encode_frame(*code, payload).unwrap());
+        }
+        let decoded = feed_all(&mut decoder, &burst);
+        assert_eq!(decoded.len(), frames.len());
+        for (command, (code, payload)) in decoded.iter().zip(frames.iter()) {
+            assert_eq!(command.code, *code);
+            assert_eq!(command.payload.as_slice(), *payload);
+        }
+        assert!(!decoder.in_frame());
+    }
+
+    #[test]
+    fn corrupt_frames_are_dropped() {
+        let mut decoder = FrameDecoder::new();
+
+        // a flipped bit anywhere after START fails the checksum
+        let good = encode_frame(b'S', b"42").unwrap();
+        for index in 1..good.len() {
+            let mut bad = good.clone();
+            bad[index] ^= 0x04;
+            assert!(feed_all(&mut decoder, &bad).is_empty(), "byte {} flipped", index);
+            decoder.reset();
+        }
+
+        // a length too long to be real gives up on the frame there
+        assert!(feed_all(&mut decoder, &[FRAME_START, b'S', MAX_PAYLOAD as u8 + 1]).is_empty());
+        assert!(!decoder.in_frame());
+
+        // and after a bad one the next good frame still gets through
+        let mut bytes = good.clone();
+        bytes[3] ^= 0x01;
+        bytes.extend_from_slice(&good).unwrap();
+        assert_eq!(feed_all(&mut decoder, &bytes).len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use microbit::gpio::DisplayPins;
+use microbit::hal::timer::Instance;
+use microbit::hal::Timer;
+
+#[cfg(not(feature = "nonblocking-display"))]
+use microbit::display::blocking::Display;
+
+#[cfg(feature = "nonblocking-display")]
+use core::cell::RefCell;
+#[cfg(feature = "nonblocking-display")]
+use microbit::hal::prelude::*;
+#[cfg(feature = "nonblocking-display")]
+use cortex_m::interrupt::Mutex;
+#[cfg(feature = "nonblocking-display")]
+use microbit::display::nonblocking::{Display, GreyscaleImage};
+#[cfg(feature = "nonblocking-display")]
+use microbit::pac::{interrupt, NVIC};
+
+// how long a frame of the display lasts, and with the blocking display each pass of the loop
+pub const FRAME_MS: u32 = 30;
+// brightest a cell or the whole display can be, levels run from 0 (off) up to this
+pub const MAX_LEVEL: u8 = 9;
+// the blocking display only switches leds on or off, so brightness comes from lighting
+// dimmer cells on fewer of these back to back scans
+#[cfg(not(feature = "nonblocking-display"))]
+const BRIGHTNESS_SCANS: u32 = 3;
+
+// the timer interrupt that refreshes the display, only TIMER0 to TIMER2 can drive it
+#[cfg(feature = "nonblocking-display")]
+pub type RefreshTimer = microbit::pac::TIMER2;
+
+// shared with the interrupt, which only ever touches it inside a critical section
+#[cfg(feature = "nonblocking-display")]
+static DISPLAY: Mutex<RefCell<Option<Display<RefreshTimer>>>> = Mutex::new(RefCell::new(None));
+
+#[cfg(feature = "nonblocking-display")]
+#[interrupt]
+fn TIMER2() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            display.handle_display_event();
+        }
+    });
+}
+
+/*
+The 5x5 display, whichever way it's driven.
+- by default it's the blocking display, which only lights while show is running, so every
+  frame holds up the caller for as long as it's on
+- with the nonblocking-display feature a timer interrupt keeps it refreshed in the background,
+  with real greyscale, and showing a frame only swaps the buffer the interrupt draws from and returns.
+  The game loop then goes round as fast as it can, polling input, and frame_due says when a new
+  frame is wanted. hold_levels is for the screens that do want to wait each frame out
+*/
+pub struct Screen {
+    #[cfg(not(feature = "nonblocking-display"))]
+    display: Display,
+}
+
+impl Screen {
+    #[cfg(not(feature = "nonblocking-display"))]
+    pub fn new(pins: DisplayPins) -> Screen {
+        Screen { display: Display::new(pins) }
+    }
+
+    #[cfg(feature = "nonblocking-display")]
+    pub fn new(pins: DisplayPins, refresh_timer: RefreshTimer) -> Screen {
+        let display = Display::new(refresh_timer, pins);
+        cortex_m::interrupt::free(|cs| DISPLAY.borrow(cs).replace(Some(display)));
+        // the display is in place before its interrupt can fire
+        unsafe { NVIC::unmask(microbit::pac::Interrupt::TIMER2) };
+        Screen {}
+    }
+
+    #[cfg(not(feature = "nonblocking-display"))]
+    pub fn show_levels<T: Instance>(&mut self, timer: &mut Timer<T>, frame: [[u8; 5]; 5], brightness: u8) {
+        // shows a frame of brightness levels 0-9 for FRAME_MS, brighter cells stay lit on more scans.
+        // the whole frame is scaled down by the display brightness, but a lit cell never goes out
+        let frame = scale_levels(frame, brightness);
+        for scan in 0..BRIGHTNESS_SCANS {
+            let threshold = (scan * MAX_LEVEL as u32 / BRIGHTNESS_SCANS) as u8;
+            let mut lit = [[0; 5]; 5];
+            for (x, row) in frame.iter().enumerate() {
+                for (y, level) in row.iter().enumerate() {
+                    lit[x][y] = (*level > threshold) as u8;
+                }
+            }
+            self.display.show(timer, lit, FRAME_MS / BRIGHTNESS_SCANS);
+        }
+    }
+
+    #[cfg(feature = "nonblocking-display")]
+    pub fn show_levels<T: Instance>(&mut self, _timer: &mut Timer<T>, frame: [[u8; 5]; 5], brightness: u8) {
+        // puts a frame of brightness levels 0-9 up, scaled down by the display brightness, and returns straight away.
+        // it stays up until the next one
+        self.put(scale_levels(frame, brightness));
+    }
+
+    #[cfg(not(feature = "nonblocking-display"))]
+    pub fn hold_levels<T: Instance>(&mut self, timer: &mut Timer<T>, frame: [[u8; 5]; 5], brightness: u8) {
+        // shows a frame for FRAME_MS and only then returns, which show_levels already does here
+        self.show_levels(timer, frame, brightness);
+    }
+
+    #[cfg(feature = "nonblocking-display")]
+    pub fn hold_levels<T: Instance>(&mut self, timer: &mut Timer<T>, frame: [[u8; 5]; 5], brightness: u8) {
+        // shows a frame for FRAME_MS and only then returns, for the screens that count their time in frames
+        self.put(scale_levels(frame, brightness));
+        timer.delay_ms(FRAME_MS);
+    }
+
+    #[cfg(not(feature = "nonblocking-display"))]
+    pub fn frame_due<T: Instance>(&mut self, _timer: &mut Timer<T>) -> bool {
+        // every pass of the loop is a whole frame, show_levels has just spent FRAME_MS on one
+        true
+    }
+
+    #[cfg(feature = "nonblocking-display")]
+    pub fn frame_due<T: Instance>(&mut self, timer: &mut Timer<T>) -> bool {
+        // whether FRAME_MS has gone by since the last frame, starting the count for the next one if it has.
+        // the timer stops on zero once it runs out, and anything else that used it in between leaves it
+        // that way too, so that counts as a frame gone by as well
+        if timer.read() != 0 {
+            return false;
+        }
+        timer.start(FRAME_MS * 1_000);
+        true
+    }
+
+    #[cfg(feature = "nonblocking-display")]
+    fn put(&mut self, levels: [[u8; 5]; 5]) {
+        // swaps what the interrupt draws, straight away
+        let image = GreyscaleImage::new(&levels);
+        cortex_m::interrupt::free(|cs| {
+            if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                display.show(&image);
+            }
+        });
+    }
+
+    #[cfg(not(feature = "nonblocking-display"))]
+    pub fn clear(&mut self) {
+        self.display.clear();
+    }
+
+    #[cfg(feature = "nonblocking-display")]
+    pub fn clear(&mut self) {
+        cortex_m::interrupt::free(|cs| {
+            if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                display.clear();
+            }
+        });
+    }
+}
+
+fn scale_levels(frame: [[u8; 5]; 5], brightness: u8) -> [[u8; 5]; 5] {
+    // a frame turned down to the display brightness, where a lit cell never goes out
+    let mut scaled = frame;
+    for level in scaled.iter_mut().flat_map(|row| row.iter_mut()) {
+        if *level != 0 {
+            *level = ((*level).min(MAX_LEVEL) as u32 * brightness as u32 / MAX_LEVEL as u32).max(1) as u8;
+        }
+    }
+    scaled
+}
@@ -1,4 +1,5 @@
 use core::fmt;
+use core::ptr::addr_of_mut;
 use embedded_hal::blocking::serial as bserial;
 use embedded_hal::serial;
 use microbit::hal::uarte::{Error, Instance, Uarte, UarteRx, UarteTx};
@@ -11,7 +12,7 @@ pub struct UartePort<T: Instance>(UarteTx<T>, UarteRx<T>);
 impl<T: Instance> UartePort<T> {
     pub fn new(serial: Uarte<T>) -> UartePort<T> {
         let (tx, rx) = serial
-            .split(unsafe { &mut TX_BUF }, unsafe { &mut RX_BUF })
+            .split(unsafe { &mut *addr_of_mut!(TX_BUF) }, unsafe { &mut *addr_of_mut!(RX_BUF) })
             .unwrap();
         UartePort(tx, rx)
     }
@@ -0,0 +1,70 @@
+use microbit::hal::gpio::{Level, Output, Pin, PushPull, p0::P0_00, Disconnected};
+use microbit::hal::prelude::*;
+use microbit::hal::Timer;
+use microbit::pac::TIMER3;
+
+use snake::game::GameEvent;
+#[cfg(feature = "metronome")]
+use snake::game::{metronome_click_ms, metronome_hz};
+
+// short high blip for a nugget
+const ATE_TONE: (u32, u32) = (1760, 60);
+// falling notes when the snake dies
+const DIED_TONES: [(u32, u32); 3] = [(440, 120), (330, 120), (220, 240)];
+// rising notes when the board is full
+const WON_TONES: [(u32, u32); 4] = [(523, 100), (659, 100), (784, 100), (1047, 240)];
+// a high beep then a low one when a time attack runs out
+const TIME_UP_TONES: [(u32, u32); 2] = [(880, 150), (440, 300)];
+
+pub struct Speaker {
+    pin: Pin<Output<PushPull>>,
+    timer: Timer<TIMER3>,
+}
+
+impl Speaker {
+    pub fn new(pin: P0_00<Disconnected>, timer: TIMER3) -> Speaker {
+        Speaker {
+            pin: pin.into_push_pull_output(Level::Low).degrade(),
+            timer: Timer::new(timer),
+        }
+    }
+
+    pub fn tone(&mut self, hz: u32, ms: u32) {
+        // square wave by toggling the pin, blocks for the length of the note
+        let half_period_us = 500_000 / hz;
+        for _ in 0..hz * ms / 1000 {
+            self.pin.set_high().ok();
+            self.timer.delay_us(half_period_us);
+            self.pin.set_low().ok();
+            self.timer.delay_us(half_period_us);
+        }
+    }
+
+    #[cfg(feature = "metronome")]
+    pub fn click(&mut self, tick_ms: u32) {
+        // the metronome's tick, a short blip that's higher the faster the game is going
+        self.tone(metronome_hz(tick_ms), metronome_click_ms(tick_ms));
+    }
+
+    pub fn play(&mut self, event: GameEvent) {
+        // the sound that goes with each game event
+        match event {
+            GameEvent::Ate => self.tone(ATE_TONE.0, ATE_TONE.1),
+            GameEvent::Died(_) => {
+                for &(hz, ms) in DIED_TONES.iter() {
+                    self.tone(hz, ms);
+                }
+            }
+            GameEvent::Won => {
+                for &(hz, ms) in WON_TONES.iter() {
+                    self.tone(hz, ms);
+                }
+            }
+            GameEvent::TimeUp => {
+                for &(hz, ms) in TIME_UP_TONES.iter() {
+                    self.tone(hz, ms);
+                }
+            }
+        }
+    }
+}
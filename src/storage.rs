@@ -0,0 +1,63 @@
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use microbit::hal::nvmc::Nvmc;
+use microbit::pac::NVMC;
+
+use snake::game::{StateBlob, STATE_BLOB_LEN};
+
+const PAGE_SIZE: usize = 4096;
+
+extern "C" {
+    // reserved flash pages, see the HIGHSCORE and SAVEGAME regions in memory.x
+    #[link_name = "_savegame"]
+    static mut SAVEGAME: [u32; PAGE_SIZE / 4];
+    #[link_name = "_highscore"]
+    static mut HIGHSCORE: [u32; PAGE_SIZE / 4];
+}
+
+// the flash controller is handed to whichever page is being worked on, and taken back after
+pub struct Storage(Option<NVMC>);
+
+impl Storage {
+    pub fn new(nvmc: NVMC) -> Storage {
+        Storage(Some(nvmc))
+    }
+
+    fn with_page<R>(&mut self, page: *mut [u32; PAGE_SIZE / 4], f: impl FnOnce(&mut Nvmc<NVMC>) -> R) -> R {
+        // only one page is ever borrowed at a time, so the static is never aliased
+        let page = unsafe { &mut *page };
+        let mut flash = Nvmc::new(self.0.take().unwrap(), page);
+        let result = f(&mut flash);
+        self.0 = Some(flash.free().0);
+        result
+    }
+
+    pub fn read_state(&mut self) -> StateBlob {
+        // an erased page reads back as all 0xFF, which restore rejects
+        let mut bytes = [0xFF; STATE_BLOB_LEN];
+        self.with_page(core::ptr::addr_of_mut!(SAVEGAME), |flash| flash.read(0, &mut bytes).ok());
+        StateBlob::from_bytes(bytes)
+    }
+
+    pub fn write_state(&mut self, blob: &StateBlob) -> bool {
+        // flash can only clear bits, so the whole page has to be erased first
+        self.with_page(core::ptr::addr_of_mut!(SAVEGAME), |flash| {
+            flash.erase(0, PAGE_SIZE as u32).is_ok() && flash.write(0, blob.as_bytes()).is_ok()
+        })
+    }
+
+    pub fn load_high_score(&mut self) -> u32 {
+        // an erased page reads back as all ones, which means no score has been saved yet
+        let mut bytes = [0xFF; 4];
+        self.with_page(core::ptr::addr_of_mut!(HIGHSCORE), |flash| flash.read(0, &mut bytes).ok());
+        match u32::from_le_bytes(bytes) {
+            u32::MAX => 0,
+            score => score,
+        }
+    }
+
+    pub fn save_high_score(&mut self, score: u32) -> bool {
+        self.with_page(core::ptr::addr_of_mut!(HIGHSCORE), |flash| {
+            flash.erase(0, PAGE_SIZE as u32).is_ok() && flash.write(0, &score.to_le_bytes()).is_ok()
+        })
+    }
+}